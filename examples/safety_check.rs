@@ -0,0 +1,34 @@
+use bft_test::whitebox::collection::safety::check_trace;
+use std::{env, process};
+
+// Check the BFT safety of a database recorded by the actuator, such as
+// `cargo run --example safety_check -- db/test.db`.
+fn main() {
+    let db_path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: safety_check <db path>");
+            process::exit(2);
+        }
+    };
+    let reports = check_trace(&db_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let mut safe = true;
+    for report in reports.iter() {
+        for violation in report.violations.iter() {
+            safe = false;
+            println!("Run {:?}: {:?}", report.run_id, violation);
+        }
+    }
+    println!(
+        "Check {:?} runs, {:?} unsafe",
+        reports.len(),
+        reports.iter().filter(|r| !r.is_safe()).count()
+    );
+    if !safe {
+        process::exit(1);
+    }
+}
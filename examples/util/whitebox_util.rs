@@ -1,6 +1,7 @@
 use bft_core::types as bft;
 use bft_test::whitebox::types::*;
 use crossbeam_channel::{Receiver, Sender};
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub(crate) struct TestSupport {
@@ -59,34 +60,11 @@ impl Support for TestSupport {
     }
 
     fn recv(&self) -> FrameRecv {
-        match self.recv.recv().unwrap() {
-            bft::BftMsg::Proposal(p) => {
-                return FrameRecv::Proposal(Proposal {
-                    height: p.height,
-                    round: p.round,
-                    content: p.content,
-                    lock_round: p.lock_round,
-                    lock_votes: from_bft_vote(p.lock_votes),
-                    proposer: p.proposer,
-                })
-            }
-            bft::BftMsg::Vote(v) => {
-                let vote_type = if v.vote_type == bft::VoteType::Prevote {
-                    VoteType::Prevote
-                } else {
-                    VoteType::Precommit
-                };
+        from_bft_msg(self.recv.recv().unwrap())
+    }
 
-                return FrameRecv::Vote(Vote {
-                    height: v.height,
-                    round: v.round,
-                    vote_type,
-                    proposal: v.proposal,
-                    voter: v.voter,
-                });
-            }
-            _ => panic!("Invalid msg type!"),
-        }
+    fn recv_timeout(&self, timeout: Duration) -> Option<FrameRecv> {
+        self.recv.recv_timeout(timeout).ok().map(from_bft_msg)
     }
 
     fn try_get_commit(&self) -> Option<Commit> {
@@ -104,6 +82,17 @@ impl Support for TestSupport {
         }
     }
 
+    fn wait_commit(&self, timeout: Duration) -> Option<Commit> {
+        self.recv_commit.recv_timeout(timeout).ok().map(|c| {
+            println!("Get commit at height {:?}", c.height);
+            Commit {
+                height: c.height,
+                result: c.proposal,
+                node: 0 as u8,
+            }
+        })
+    }
+
     fn stop(&self) {}
 
     fn cal_proposer(&self, height: u64, round: u64) -> usize {
@@ -125,6 +114,37 @@ impl TestSupport {
     }
 }
 
+fn from_bft_msg(msg: bft::BftMsg) -> FrameRecv {
+    match msg {
+        bft::BftMsg::Proposal(p) => {
+            return FrameRecv::Proposal(Proposal {
+                height: p.height,
+                round: p.round,
+                content: p.content,
+                lock_round: p.lock_round,
+                lock_votes: from_bft_vote(p.lock_votes),
+                proposer: p.proposer,
+            })
+        }
+        bft::BftMsg::Vote(v) => {
+            let vote_type = if v.vote_type == bft::VoteType::Prevote {
+                VoteType::Prevote
+            } else {
+                VoteType::Precommit
+            };
+
+            return FrameRecv::Vote(Vote {
+                height: v.height,
+                round: v.round,
+                vote_type,
+                proposal: v.proposal,
+                voter: v.voter,
+            });
+        }
+        _ => panic!("Invalid msg type!"),
+    }
+}
+
 fn into_bft_vote(lock_votes: Vec<Vote>) -> Vec<bft::Vote> {
     let mut res = Vec::new();
     if lock_votes.len() != 0 {
@@ -1,11 +1,11 @@
 pub mod util;
 
 use bft_core::{types::*, Core};
-use bft_test::whitebox::actuator::Actuator;
+use bft_test::whitebox::{actuator::Actuator, correctness::loader::load_scenario_dir};
 use crossbeam_channel::{select, unbounded, Receiver, Sender};
 use env_logger::Builder;
 use log::LevelFilter::Info;
-use std::thread;
+use std::{env, thread};
 use util::whitebox_util::{generate_authority, TestSupport};
 
 const INIT_HEIGHT: u64 = 0;
@@ -84,7 +84,24 @@ fn main() {
         generate_authority(),
         "db/test.db",
     );
-    // let case = bft_test::test_case::lock_proposal();
+    // Reproduce a failed test with the seed it prints.
+    if let Some(seed) = env::var("BFT_TEST_SEED").ok().and_then(|s| s.parse().ok()) {
+        test.set_seed(seed);
+    }
+    // let case = bft_test::test_case::lock_proposal(4, test.seed());
     // let _ = test.proc_test(case).map_err(|err| panic!("bft error {:?}", err));
+
+    // Run all the scenario files in a directory if it is given, for example
+    // `cargo run --example whitebox_test -- examples/scenarios`.
+    if let Some(dir) = env::args().nth(1) {
+        let files = load_scenario_dir(&dir).unwrap_or_else(|err| panic!("{}", err));
+        for (path, file) in files.iter() {
+            println!("Run scenario file {:?}", path);
+            let _ = test
+                .proc_scenario_file(file)
+                .map_err(|err| panic!("bft error in {:?}: {:?}", path, err));
+        }
+        return;
+    }
     let _ = test.all_test().map_err(|err| panic!("bft error {:?}", err));
 }
@@ -0,0 +1,411 @@
+use crate::blackbox::{
+    network::{Network, NetworkStats},
+    types::{BlackboxSupport, ClusterReport, CommitRecord},
+};
+use crate::error::BftError;
+use crate::whitebox::types::{Address, BftResult, Commit, Feed, FrameRecv, FrameSend, Status};
+
+use log::{debug, error, info};
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A blackbox testing cluster. It starts a consensus engine instance for each address
+/// in the authority list, carries the messages between them through a simulated
+/// network, and watches the commits of all the nodes.
+pub struct Cluster<T> {
+    nodes: Vec<T>,
+    authority_list: Vec<Address>,
+    network: Network,
+    heights: Vec<u64>,
+    feeds: HashMap<u64, Vec<Vec<u8>>>,
+    results: HashMap<u64, Vec<u8>>,
+    report: ClusterReport,
+    interval: Duration,
+    stime: Instant,
+}
+
+impl<T> Cluster<T>
+where
+    T: BlackboxSupport,
+{
+    /// A function to start a testing cluster. The `factory` creates the node with the
+    /// index in the `authority_list`. The `height` is the initial height, every node
+    /// receives a status of it at start and begins consensus from the next height.
+    pub fn start<F>(authority_list: Vec<Address>, height: u64, mut factory: F) -> Self
+    where
+        F: FnMut(usize, &[Address]) -> T,
+    {
+        let nodes = (0..authority_list.len())
+            .map(|index| factory(index, &authority_list))
+            .collect::<Vec<_>>();
+        let mut cluster = Cluster {
+            network: Network::new(nodes.len()),
+            heights: vec![height + 1; nodes.len()],
+            nodes,
+            authority_list,
+            feeds: HashMap::new(),
+            results: HashMap::new(),
+            report: ClusterReport::default(),
+            interval: Duration::from_millis(1),
+            stime: Instant::now(),
+        };
+
+        info!("Start a cluster of {:?} nodes", cluster.nodes.len());
+        for index in 0..cluster.nodes.len() {
+            cluster.send_status(index, height);
+            cluster.send_feed(index, height + 1);
+        }
+        cluster
+    }
+
+    /// A function to set the seed of the random faults of the simulated network. The seed
+    /// is random by default, and it is logged when `run` fails. The seed determines the
+    /// faults of each packet, such as the drops and the latencies, but the packets are
+    /// delivered by the wall clock and the nodes run on their own, so the same seed does
+    /// not always reproduce a failure.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.network.set_seed(seed);
+        self
+    }
+
+    /// A function to get the seed of the random faults of the simulated network.
+    pub fn seed(&self) -> u64 {
+        self.network.seed()
+    }
+
+    /// A function to set the poll interval of the cluster. The default interval is
+    /// 1 millisecond.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// A function to run the cluster until all the nodes commit the `height`. Return
+    /// `BftError::NoCommit` if any node does not commit it before the `timeout`, and
+    /// other `BftError` if the commits of nodes break the agreement.
+    pub fn run(&mut self, height: u64, timeout: Duration) -> BftResult<&ClusterReport> {
+        if let Err(err) = self.run_until(height, timeout) {
+            error!(
+                "Cluster failed with seed {:?}: {:?}",
+                self.network.seed(),
+                err
+            );
+            return Err(err);
+        }
+        Ok(&self.report)
+    }
+
+    fn run_until(&mut self, height: u64, timeout: Duration) -> BftResult<()> {
+        let deadline = self.stime.elapsed() + timeout;
+        while self.heights.iter().any(|h| *h <= height) {
+            let now = self.stime.elapsed();
+            if now > deadline {
+                let lowest = self.heights.iter().min().cloned().unwrap_or_default();
+                return Err(BftError::NoCommit(lowest));
+            }
+
+            for index in 0..self.nodes.len() {
+                while let Some(msg) = self.nodes[index].try_recv() {
+                    let msg = match msg {
+                        FrameRecv::Proposal(p) => FrameSend::Proposal(p),
+                        FrameRecv::Vote(v) => FrameSend::Vote(v),
+                    };
+                    self.network.broadcast(index, msg, now);
+                }
+            }
+            for packet in self.network.deliver(now).into_iter() {
+                debug!(
+                    "Deliver from {:?} to {:?}: {:?}",
+                    packet.from, packet.to, packet.msg
+                );
+                self.nodes[packet.to].send(packet.msg);
+            }
+            for index in 0..self.nodes.len() {
+                while let Some(commit) = self.nodes[index].try_get_commit() {
+                    self.check_commit(index, commit)?;
+                }
+            }
+            thread::sleep(self.interval);
+        }
+        Ok(())
+    }
+
+    /// A function to get the simulated network of the cluster. Configure the links and
+    /// the partitions by it before `run`.
+    pub fn network_mut(&mut self) -> &mut Network {
+        &mut self.network
+    }
+
+    /// A function to get the statistics of the simulated network.
+    pub fn network_stats(&self) -> &NetworkStats {
+        self.network.stats()
+    }
+
+    /// A function to get the report of the cluster.
+    pub fn report(&self) -> &ClusterReport {
+        &self.report
+    }
+
+    /// A function to stop all the nodes in the cluster.
+    pub fn stop(&self) {
+        for node in self.nodes.iter() {
+            node.stop();
+        }
+    }
+
+    fn check_commit(&mut self, index: usize, commit: Commit) -> BftResult<()> {
+        let height = commit.height;
+        debug!("Node {:?} commit {:?}", index, commit);
+        if height < self.heights[index] {
+            return Err(BftError::MultipleCommit(height));
+        }
+        if height > self.heights[index] {
+            return Err(BftError::MislaidCommit(height));
+        }
+        if !self
+            .feeds
+            .get(&height)
+            .map(|feeds| feeds.contains(&commit.result))
+            .unwrap_or(false)
+        {
+            return Err(BftError::CommitIncorrect(height));
+        }
+        if self
+            .results
+            .entry(height)
+            .or_insert_with(|| commit.result.clone())
+            != &commit.result
+        {
+            return Err(BftError::CommitDiff(height));
+        }
+
+        self.report.commits.push(CommitRecord {
+            node: index,
+            height,
+            result: commit.result,
+            elapsed: self.stime.elapsed(),
+        });
+        self.heights[index] = height + 1;
+        if self.heights.iter().all(|h| *h > height) {
+            info!("All nodes commit height {:?}", height);
+            self.report.height_time.push((height, self.stime.elapsed()));
+        }
+        self.send_status(index, height);
+        self.send_feed(index, height + 1);
+        Ok(())
+    }
+
+    fn send_status(&mut self, index: usize, height: u64) {
+        self.nodes[index].send(FrameSend::Status(Status {
+            height,
+            authority_list: self.authority_list.clone(),
+        }));
+    }
+
+    fn send_feed(&mut self, index: usize, height: u64) {
+        // the full index keeps the feeds of a height distinct for any cluster size
+        let mut proposal = height.to_be_bytes().to_vec();
+        proposal.extend_from_slice(&(index as u64).to_be_bytes());
+        self.feeds.entry(height).or_default().push(proposal.clone());
+        self.nodes[index].send(FrameSend::Feed(Feed { height, proposal }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::blackbox::network::{Latency, LinkConfig, Partition};
+    use crate::whitebox::types::Proposal;
+    use std::collections::{HashSet, VecDeque};
+    use std::convert::TryFrom;
+    use std::sync::Mutex;
+
+    // A node that commits the proposal of the leader once it receives it.
+    struct LeaderNode {
+        index: usize,
+        node_num: usize,
+        evil: bool,
+        state: Mutex<LeaderState>,
+    }
+
+    #[derive(Default)]
+    struct LeaderState {
+        height: u64,
+        committed: u64,
+        outbox: VecDeque<FrameRecv>,
+        commits: VecDeque<Commit>,
+        pending: Vec<Proposal>,
+    }
+
+    impl LeaderNode {
+        fn new(index: usize, node_num: usize, evil: bool) -> Self {
+            LeaderNode {
+                index,
+                node_num,
+                evil,
+                state: Mutex::new(LeaderState::default()),
+            }
+        }
+
+        fn commit(&self, state: &mut LeaderState, result: Vec<u8>) {
+            if state.committed >= state.height {
+                return;
+            }
+            state.committed = state.height;
+            state.commits.push_back(Commit {
+                node: u8::try_from(self.index).expect("Node index beyond u8"),
+                height: state.height,
+                result: if self.evil { vec![0xff] } else { result },
+            });
+        }
+    }
+
+    impl BlackboxSupport for LeaderNode {
+        fn send(&self, msg: FrameSend) {
+            let mut state = self.state.lock().unwrap();
+            match msg {
+                FrameSend::Status(s) => {
+                    state.height = s.height + 1;
+                    let height = state.height;
+                    let pending = state.pending.clone();
+                    state.pending.retain(|p| p.height > height);
+                    if let Some(p) = pending.into_iter().find(|p| p.height == height) {
+                        self.commit(&mut state, p.content);
+                    }
+                }
+                FrameSend::Feed(f) => {
+                    if f.height == state.height && f.height as usize % self.node_num == self.index {
+                        state.outbox.push_back(FrameRecv::Proposal(Proposal {
+                            height: f.height,
+                            round: 0,
+                            content: f.proposal.clone(),
+                            proposer: vec![self.index as u8],
+                            lock_round: None,
+                            lock_votes: Vec::new(),
+                        }));
+                        self.commit(&mut state, f.proposal);
+                    }
+                }
+                FrameSend::Proposal(p) => {
+                    if p.height == state.height {
+                        self.commit(&mut state, p.content);
+                    } else if p.height > state.height {
+                        state.pending.push(p);
+                    }
+                }
+                FrameSend::Vote(_) => (),
+            }
+        }
+
+        fn try_recv(&self) -> Option<FrameRecv> {
+            self.state.lock().unwrap().outbox.pop_front()
+        }
+
+        fn try_get_commit(&self) -> Option<Commit> {
+            self.state.lock().unwrap().commits.pop_front()
+        }
+
+        fn stop(&self) {}
+    }
+
+    fn generate_authority(num: u8) -> Vec<Address> {
+        (0..num).map(|i| vec![i]).collect()
+    }
+
+    #[test]
+    fn test_cluster_agreement() {
+        let mut cluster = Cluster::start(generate_authority(4), 0, |index, authority_list| {
+            LeaderNode::new(index, authority_list.len(), false)
+        });
+        let report = cluster.run(5, Duration::from_secs(5)).unwrap().clone();
+        cluster.stop();
+
+        assert_eq!(
+            report
+                .height_time
+                .iter()
+                .map(|h| h.0)
+                .take(5)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+        for height in 1..=5 {
+            let commits = report.commits_at(height);
+            assert_eq!(commits.len(), 4);
+            assert!(commits.iter().all(|c| c.result == commits[0].result));
+        }
+    }
+
+    #[test]
+    fn test_cluster_with_faults() {
+        let mut cluster = Cluster::start(generate_authority(4), 0, |index, authority_list| {
+            LeaderNode::new(index, authority_list.len(), false)
+        })
+        .with_seed(7);
+        assert_eq!(cluster.seed(), 7);
+        cluster.network_mut().set_default_link(LinkConfig {
+            latency: Latency::Uniform(Duration::from_millis(1), Duration::from_millis(5)),
+            duplicate: 0.5,
+            reorder: Duration::from_millis(5),
+            ..LinkConfig::default()
+        });
+        cluster.run(3, Duration::from_secs(5)).unwrap();
+        assert!(cluster.network_stats().duplicated > 0);
+
+        cluster.network_mut().add_partition(Partition::new(
+            vec![vec![0, 1], vec![2, 3]],
+            Duration::from_millis(0),
+            Duration::from_secs(3600),
+        ));
+        match cluster.run(10, Duration::from_millis(200)) {
+            Err(BftError::NoCommit(_)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert!(cluster.network_stats().partitioned > 0);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_cluster_mock_node() {
+        use crate::blackbox::types::SupportNode;
+        use crate::whitebox::mock::MockNode;
+
+        let mut cluster = Cluster::start(generate_authority(4), 0, |index, _| {
+            SupportNode(MockNode::new(vec![index as u8]))
+        })
+        .with_seed(7);
+        cluster.network_mut().set_default_link(LinkConfig {
+            latency: Latency::Uniform(Duration::from_millis(1), Duration::from_millis(5)),
+            ..LinkConfig::default()
+        });
+        let report = cluster.run(5, Duration::from_secs(10)).unwrap().clone();
+        cluster.stop();
+        for height in 1..=5 {
+            let commits = report.commits_at(height);
+            assert_eq!(commits.len(), 4);
+            assert!(commits.iter().all(|c| c.result == commits[0].result));
+        }
+    }
+
+    #[test]
+    fn test_cluster_feeds() {
+        let authority_list = (0..300u16).map(|i| i.to_be_bytes().to_vec()).collect();
+        let cluster = Cluster::start(authority_list, 0, |index, authority_list| {
+            LeaderNode::new(index, authority_list.len(), false)
+        });
+        let feeds = cluster.feeds[&1].iter().collect::<HashSet<_>>();
+        assert_eq!(feeds.len(), 300);
+    }
+
+    #[test]
+    fn test_cluster_disagreement() {
+        let mut cluster = Cluster::start(generate_authority(4), 0, |index, authority_list| {
+            LeaderNode::new(index, authority_list.len(), index == 3)
+        });
+        match cluster.run(3, Duration::from_secs(5)) {
+            Err(BftError::CommitIncorrect(1)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+}
@@ -1 +1,6 @@
-
+///
+pub mod cluster;
+///
+pub mod network;
+///
+pub mod types;
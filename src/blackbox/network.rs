@@ -0,0 +1,459 @@
+use crate::whitebox::types::FrameSend;
+
+use log::debug;
+use rand::distributions::{Distribution, Normal};
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Duration;
+
+/// The latency distribution of a link.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Latency {
+    /// A fixed latency.
+    Fixed(Duration),
+    /// A latency uniformly distributed in `[low, high)`.
+    Uniform(Duration, Duration),
+    /// A latency normally distributed with the mean and the standard deviation.
+    /// A negative sample is taken as zero.
+    Normal(Duration, Duration),
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::Fixed(Duration::from_millis(0))
+    }
+}
+
+impl Latency {
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        match self {
+            Latency::Fixed(d) => *d,
+            Latency::Uniform(low, high) => {
+                if high <= low {
+                    *low
+                } else {
+                    Duration::from_micros(rng.gen_range(as_micros(*low), as_micros(*high)))
+                }
+            }
+            Latency::Normal(mean, std_dev) => {
+                let sample =
+                    Normal::new(as_micros(*mean) as f64, as_micros(*std_dev) as f64).sample(rng);
+                Duration::from_micros(sample.max(0.0) as u64)
+            }
+        }
+    }
+}
+
+/// The fault configuration of a directed link between two nodes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkConfig {
+    /// The latency distribution.
+    pub latency: Latency,
+    /// The probability to lose a packet.
+    pub loss: f64,
+    /// The probability to deliver a packet twice.
+    pub duplicate: f64,
+    /// The reordering window. Every packet gets an extra random delay in it, so the
+    /// packets sent within the window may arrive out of order.
+    pub reorder: Duration,
+}
+
+/// A scheduled network partition. From `start` to `end`, the nodes can only talk with
+/// the nodes in the same group, and a node not in any group is isolated from all the
+/// others. Both `start` and `end` are durations from the cluster start.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Partition {
+    /// The groups of node index.
+    pub groups: Vec<Vec<usize>>,
+    /// The time the partition starts.
+    pub start: Duration,
+    /// The time the partition heals.
+    pub end: Duration,
+}
+
+impl Partition {
+    /// A function to create a partition of the groups from `start` to `end`.
+    pub fn new(groups: Vec<Vec<usize>>, start: Duration, end: Duration) -> Self {
+        Partition { groups, start, end }
+    }
+
+    fn separates(&self, from: usize, to: usize, now: Duration) -> bool {
+        if now < self.start || now >= self.end {
+            return false;
+        }
+        !self
+            .groups
+            .iter()
+            .any(|group| group.contains(&from) && group.contains(&to))
+    }
+}
+
+/// The statistics of a network.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// The number of packets sent.
+    pub sent: u64,
+    /// The number of packets delivered.
+    pub delivered: u64,
+    /// The number of packets lost by link loss.
+    pub lost: u64,
+    /// The number of packets dropped by partitions.
+    pub partitioned: u64,
+    /// The number of duplicated packets.
+    pub duplicated: u64,
+}
+
+/// An in-process simulated network between the nodes of a cluster. Every message
+/// broadcast by a node goes through the link to each of the other nodes, which may
+/// delay, lose, duplicate or reorder it, and the scheduled partitions cut the links.
+#[derive(Debug)]
+pub struct Network {
+    node_num: usize,
+    default_link: LinkConfig,
+    links: HashMap<(usize, usize), LinkConfig>,
+    partitions: Vec<Partition>,
+    queue: BinaryHeap<Reverse<Packet>>,
+    seq: u64,
+    stats: NetworkStats,
+    seed: u64,
+    rng: StdRng,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Packet {
+    pub(crate) deliver_at: Duration,
+    pub(crate) seq: u64,
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+    pub(crate) msg: FrameSend,
+}
+
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Packet {}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deliver_at, self.seq).cmp(&(other.deliver_at, other.seq))
+    }
+}
+
+impl Network {
+    /// A function to create a network of `node_num` nodes. All the links are perfect
+    /// before any configuration. The seed of the random faults is random, use `set_seed`
+    /// to reproduce a test.
+    pub fn new(node_num: usize) -> Self {
+        let seed = random();
+        Network {
+            node_num,
+            default_link: LinkConfig::default(),
+            links: HashMap::new(),
+            partitions: Vec::new(),
+            queue: BinaryHeap::new(),
+            seq: 0,
+            stats: NetworkStats::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// A function to set the seed of the random faults. The same seed gives the same
+    /// sequence of latency, loss and duplication samples.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// A function to get the seed of the random faults.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A function to get the number of nodes in the network.
+    pub fn node_num(&self) -> usize {
+        self.node_num
+    }
+
+    /// A function to set the configuration of all the links that are not set by
+    /// `set_link`.
+    pub fn set_default_link(&mut self, config: LinkConfig) {
+        self.default_link = config;
+    }
+
+    /// A function to set the configuration of the link from node `from` to node `to`.
+    pub fn set_link(&mut self, from: usize, to: usize, config: LinkConfig) {
+        self.links.insert((from, to), config);
+    }
+
+    /// A function to set the configuration of the links in both directions between
+    /// node `a` and node `b`.
+    pub fn set_bidirectional_link(&mut self, a: usize, b: usize, config: LinkConfig) {
+        self.links.insert((a, b), config.clone());
+        self.links.insert((b, a), config);
+    }
+
+    /// A function to schedule a partition.
+    pub fn add_partition(&mut self, partition: Partition) {
+        self.partitions.push(partition);
+    }
+
+    /// A function to check whether node `from` can reach node `to` at the time `now`.
+    pub fn is_connected(&self, from: usize, to: usize, now: Duration) -> bool {
+        !self.partitions.iter().any(|p| p.separates(from, to, now))
+    }
+
+    /// A function to get the statistics of the network.
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    /// Broadcast a message from a node to all the other nodes at the time `now`.
+    pub(crate) fn broadcast(&mut self, from: usize, msg: FrameSend, now: Duration) {
+        for to in (0..self.node_num).filter(|to| *to != from) {
+            self.send(from, to, msg.clone(), now);
+        }
+    }
+
+    /// Send a message from node `from` to node `to` through the link at the time `now`.
+    pub(crate) fn send(&mut self, from: usize, to: usize, msg: FrameSend, now: Duration) {
+        self.stats.sent += 1;
+        if !self.is_connected(from, to, now) {
+            debug!("Partition drop packet from {:?} to {:?}", from, to);
+            self.stats.partitioned += 1;
+            return;
+        }
+
+        let link = self
+            .links
+            .get(&(from, to))
+            .cloned()
+            .unwrap_or_else(|| self.default_link.clone());
+        if link.loss > 0.0 && self.rng.gen::<f64>() < link.loss {
+            debug!("Lose packet from {:?} to {:?}", from, to);
+            self.stats.lost += 1;
+            return;
+        }
+        let copies = if link.duplicate > 0.0 && self.rng.gen::<f64>() < link.duplicate {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let mut delay = link.latency.sample(&mut self.rng);
+            if link.reorder > Duration::from_millis(0) {
+                delay += Duration::from_micros(self.rng.gen_range(0, as_micros(link.reorder)));
+            }
+            self.push(Packet {
+                deliver_at: now + delay,
+                seq: 0,
+                from,
+                to,
+                msg: msg.clone(),
+            });
+        }
+    }
+
+    /// Pop all the packets that should be delivered before the time `now`. The packets
+    /// whose link is cut by a partition at the time of arrival are dropped.
+    pub(crate) fn deliver(&mut self, now: Duration) -> Vec<Packet> {
+        let mut res = Vec::new();
+        while self
+            .queue
+            .peek()
+            .map(|p| p.0.deliver_at <= now)
+            .unwrap_or(false)
+        {
+            let packet = self.queue.pop().unwrap().0;
+            if self.is_connected(packet.from, packet.to, packet.deliver_at) {
+                self.stats.delivered += 1;
+                res.push(packet);
+            } else {
+                self.stats.partitioned += 1;
+            }
+        }
+        res
+    }
+
+    fn push(&mut self, mut packet: Packet) {
+        packet.seq = self.seq;
+        self.seq += 1;
+        self.queue.push(Reverse(packet));
+    }
+}
+
+fn as_micros(d: Duration) -> u64 {
+    d.as_secs() * 1_000_000 + u64::from(d.subsec_micros())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::types::Feed;
+
+    fn generate_msg(height: u64) -> FrameSend {
+        FrameSend::Feed(Feed {
+            height,
+            proposal: vec![1, 2, 3],
+        })
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let mut network = Network::new(4);
+        network.broadcast(1, generate_msg(1), ms(0));
+        network.broadcast(2, generate_msg(2), ms(0));
+        assert_eq!(network.queue.len(), 6);
+
+        let packets = network.deliver(ms(0));
+        assert_eq!(network.queue.len(), 0);
+        assert_eq!(
+            packets.iter().map(|p| (p.from, p.to)).collect::<Vec<_>>(),
+            vec![(1, 0), (1, 2), (1, 3), (2, 0), (2, 1), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn test_latency() {
+        let mut network = Network::new(2);
+        network.set_link(
+            0,
+            1,
+            LinkConfig {
+                latency: Latency::Fixed(ms(10)),
+                ..LinkConfig::default()
+            },
+        );
+        network.set_link(
+            1,
+            0,
+            LinkConfig {
+                latency: Latency::Uniform(ms(20), ms(30)),
+                ..LinkConfig::default()
+            },
+        );
+        network.broadcast(0, generate_msg(1), ms(0));
+        network.broadcast(1, generate_msg(1), ms(0));
+        assert!(network.deliver(ms(9)).is_empty());
+        assert_eq!(network.deliver(ms(10)).len(), 1);
+        assert!(network.deliver(ms(19)).is_empty());
+        assert_eq!(network.deliver(ms(30)).len(), 1);
+
+        let mut rng = StdRng::seed_from_u64(random());
+        for _ in 0..100 {
+            let d = Latency::Normal(ms(10), ms(50)).sample(&mut rng);
+            assert!(d < ms(1000));
+        }
+    }
+
+    #[test]
+    fn test_loss_and_duplicate() {
+        let mut network = Network::new(4);
+        network.set_default_link(LinkConfig {
+            loss: 1.0,
+            ..LinkConfig::default()
+        });
+        network.broadcast(0, generate_msg(1), ms(0));
+        assert!(network.deliver(ms(0)).is_empty());
+        assert_eq!(network.stats().lost, 3);
+
+        network.set_default_link(LinkConfig {
+            duplicate: 1.0,
+            ..LinkConfig::default()
+        });
+        network.broadcast(0, generate_msg(1), ms(0));
+        assert_eq!(network.deliver(ms(0)).len(), 6);
+        assert_eq!(network.stats().duplicated, 3);
+    }
+
+    #[test]
+    fn test_seed() {
+        let sample = |seed| {
+            let mut network = Network::new(2);
+            network.set_seed(seed);
+            network.set_default_link(LinkConfig {
+                latency: Latency::Uniform(ms(0), ms(100)),
+                loss: 0.5,
+                ..LinkConfig::default()
+            });
+            for height in 0..100 {
+                network.send(0, 1, generate_msg(height), ms(0));
+            }
+            network
+                .deliver(ms(100))
+                .into_iter()
+                .map(|p| (p.seq, p.deliver_at))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sample(7), sample(7));
+        let mut network = Network::new(2);
+        network.set_seed(7);
+        assert_eq!(network.seed(), 7);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut network = Network::new(2);
+        network.set_default_link(LinkConfig {
+            reorder: ms(50),
+            ..LinkConfig::default()
+        });
+        for height in 0..100 {
+            network.send(0, 1, generate_msg(height), ms(0));
+        }
+        let packets = network.deliver(ms(50));
+        assert_eq!(packets.len(), 100);
+        assert!(packets.windows(2).any(|p| p[0].seq > p[1].seq));
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut network = Network::new(4);
+        network.add_partition(Partition::new(
+            vec![vec![0, 1], vec![2, 3]],
+            ms(5000),
+            ms(12000),
+        ));
+        assert!(network.is_connected(0, 2, ms(4999)));
+        assert!(network.is_connected(0, 1, ms(5000)));
+        assert!(!network.is_connected(0, 2, ms(5000)));
+        assert!(!network.is_connected(3, 1, ms(11999)));
+        assert!(network.is_connected(0, 2, ms(12000)));
+
+        network.broadcast(0, generate_msg(1), ms(6000));
+        assert_eq!(
+            network
+                .deliver(ms(6000))
+                .iter()
+                .map(|p| p.to)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(network.stats().partitioned, 2);
+
+        // the packet in flight is dropped if the partition starts before it arrives
+        network.set_default_link(LinkConfig {
+            latency: Latency::Fixed(ms(10)),
+            ..LinkConfig::default()
+        });
+        network.send(0, 2, generate_msg(1), ms(4995));
+        assert!(network.deliver(ms(5005)).is_empty());
+        assert_eq!(network.stats().partitioned, 3);
+    }
+}
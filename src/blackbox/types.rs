@@ -0,0 +1,74 @@
+use crate::whitebox::types::{Commit, FrameRecv, FrameSend, Support};
+
+use std::time::Duration;
+
+/// Blackbox test support. It is the adapter of one consensus engine instance in
+/// the testing cluster.
+pub trait BlackboxSupport: Send {
+    /// Send a `FrameSend` message to the node. The message is either from the framework
+    /// or from another node in the cluster.
+    fn send(&self, msg: FrameSend);
+    /// Try once to receive a `FrameRecv` message that the node broadcasts.
+    /// If there is no message, return `None`.
+    fn try_recv(&self) -> Option<FrameRecv>;
+    /// Try once to get a commit message from the node.
+    /// If it does not commit, return `None`.
+    fn try_get_commit(&self) -> Option<Commit>;
+    /// Stop the node.
+    fn stop(&self);
+}
+
+/// An adapter to drive a consensus engine of whitebox `Support` in a testing cluster. The
+/// messages of the node are received by `Support::recv_timeout` without waiting.
+#[derive(Clone, Debug)]
+pub struct SupportNode<T>(pub T);
+
+impl<T> BlackboxSupport for SupportNode<T>
+where
+    T: Support + Send,
+{
+    fn send(&self, msg: FrameSend) {
+        self.0.send(msg);
+    }
+
+    fn try_recv(&self) -> Option<FrameRecv> {
+        self.0.recv_timeout(Duration::from_millis(0))
+    }
+
+    fn try_get_commit(&self) -> Option<Commit> {
+        self.0.try_get_commit()
+    }
+
+    fn stop(&self) {
+        self.0.stop();
+    }
+}
+
+/// A commit observed from a node in the cluster.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitRecord {
+    /// The index of the node in authority list.
+    pub node: usize,
+    /// The height of commit.
+    pub height: u64,
+    /// The consensus result.
+    pub result: Vec<u8>,
+    /// The duration from the cluster start to the commit.
+    pub elapsed: Duration,
+}
+
+/// A report of a cluster test.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClusterReport {
+    /// All the commits in order of observation.
+    pub commits: Vec<CommitRecord>,
+    /// The duration for all the nodes to commit each height, in order of height.
+    pub height_time: Vec<(u64, Duration)>,
+}
+
+impl ClusterReport {
+    /// A function to get the commits of a height.
+    pub fn commits_at(&self, height: u64) -> Vec<&CommitRecord> {
+        self.commits.iter().filter(|c| c.height == height).collect()
+    }
+}
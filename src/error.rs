@@ -30,6 +30,12 @@ pub enum BftError {
     IllegalProposal(u64, u64),
     ///
     NoCommit(u64),
+    ///
+    LockDiff(u64, u64),
+    ///
+    GetNoProposal(u64, u64),
+    /// An error of the framework, such as a storage error, which stops the test.
+    Frame(FrameError),
 }
 
 impl fmt::Display for BftError {
@@ -62,22 +68,54 @@ impl fmt::Display for BftError {
                 format!("Get No {:?} Vote at Height {:?}, Round {:?}", t, h, r)
             }
             BftError::NoCommit(h) => format!("No commit at height {:?}", h),
+            BftError::GetNoProposal(h, r) => {
+                format!("Get No Proposal at Height {:?}, Round {:?}", h, r)
+            }
+            BftError::LockDiff(h, r) => format!(
+                "Lock State Different from Expectation at Height {:?}, Round {:?}",
+                h, r
+            ),
+            BftError::Frame(e) => format!("{}", e),
         };
         f.write_fmt(format_args!("BFT Error ({})!", msg))
     }
 }
 
+impl From<FrameError> for BftError {
+    fn from(e: FrameError) -> Self {
+        BftError::Frame(e)
+    }
+}
+
 ///
 #[derive(Debug)]
 pub enum FrameError {
     ///
     SQLiteErr(usize),
+    ///
+    IllegalScenario(String),
+    ///
+    LoadScenario(String),
+    ///
+    Transport(String),
+    ///
+    Codec(String),
+    ///
+    DuplicateSuite(String),
+    ///
+    Storage(String),
 }
 
 impl fmt::Display for FrameError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let msg: String = match *self {
+        let msg: String = match self {
             FrameError::SQLiteErr(i) => format!("SQLite Error {:?}", i),
+            FrameError::IllegalScenario(e) => format!("Illegal Scenario {}", e),
+            FrameError::LoadScenario(e) => format!("Load Scenario Error {}", e),
+            FrameError::Transport(e) => format!("Transport Error {}", e),
+            FrameError::Codec(e) => format!("Codec Error {}", e),
+            FrameError::DuplicateSuite(e) => format!("Duplicate Suite {}", e),
+            FrameError::Storage(e) => format!("Storage Error {}", e),
         };
         f.write_fmt(format_args!("Frame Error ({})I", msg))
     }
@@ -1,19 +1,30 @@
-use crate::error::BftError;
+use crate::error::{BftError, FrameError};
 use crate::whitebox::{
     collection::{
-        proposal_cache::ProposalCache, storage::Storage, util::Msg, vote_cache::VoteCache,
+        storage::{storage_err, Storage},
+        util::{Direction, Msg, Origin},
     },
-    correctness::test_case::*,
+    correctness::{
+        loader::ScenarioFile,
+        model_check::{
+            expect_rounds, ModelCheckConfig, ModelCheckReport, ModelChecker, RoundExpect, Violation,
+        },
+        property::{scenario_from_plans, shrink, Counterexample, PropertyConfig, ScenarioGen},
+        scenario::{Behavior, RoundPlan, Step},
+        suite::{SuiteConfig, SuiteOutcome, SuiteRegistry, SuiteResult, SuiteSummary},
+        test_case::*,
+        util::check_authority_num,
+    },
+    reference::StateMachine,
     types::*,
 };
 
-use log::{debug, info};
-use rand::{thread_rng, Rng};
+use log::{debug, error, info, warn};
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
 use time::Timespec;
 
 use std::collections::HashSet;
-use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// A whitebox testing actuator.
 pub struct Actuator<T> {
@@ -21,16 +32,20 @@ pub struct Actuator<T> {
 
     height: u64,
     round: u64,
-    lock_round: Option<u64>,
-    lock_votes: Vec<Vote>,
-    lock_proposal: Option<Vec<u8>>,
     authority_list: Vec<Address>,
     proposal: Vec<u8>,
     byzantine: Vec<Vec<u8>>,
-    sleep_ms: u64,
+    commit_timeout: Duration,
+    no_commit_window: Duration,
+    recv_timeout: Duration,
+    round_leap: bool,
+    expect_lock: Option<bool>,
+    suites: SuiteRegistry,
+    seed: u64,
+    rng: StdRng,
     storage: Storage,
-    vote_cache: VoteCache,
-    proposal_cache: ProposalCache,
+    storage_error: Option<FrameError>,
+    reference: StateMachine,
     msg_cache: HashSet<FrameRecv>,
     stime: Instant,
     htime: Timespec,
@@ -41,9 +56,12 @@ where
     T: Support + Clone + Send + 'static,
 {
     /// A function to create a new testing acutator. The `height` is the initial height
-    /// and the `round` is the initial round. The `authority_list` should be a `Vec` with
-    /// length 4. The first one in authority list should be the address of the testing
-    /// node. The `db_path` is the path of database.
+    /// and the `round` is the initial round. The `authority_list` can be of any length
+    /// from 2, and the first one in authority list should be the address of the testing
+    /// node. The `db_path` is the path of database. An existing database is reopened, and
+    /// each test is recorded as a new run in it. The seed of the actuator is random, use
+    /// `set_seed` to reproduce a test. It panics if the authority list is too short or the
+    /// database cannot be opened, use `try_new` to get the error instead.
     pub fn new(
         function: T,
         height: u64,
@@ -51,50 +69,168 @@ where
         authority_list: Vec<Address>,
         db_path: &str,
     ) -> Self {
-        Actuator {
+        Self::try_new(function, height, round, authority_list, db_path)
+            .unwrap_or_else(|e| panic!("Create actuator with {:?} failed: {}", db_path, e))
+    }
+
+    /// A function to create a new testing actuator like `new`. Return an error if the
+    /// authority list has less than 2 nodes, or the database cannot be opened, or it is
+    /// of another schema version.
+    pub fn try_new(
+        function: T,
+        height: u64,
+        round: u64,
+        authority_list: Vec<Address>,
+        db_path: &str,
+    ) -> FrameResult<Self> {
+        check_authority_num(authority_list.len())?;
+        let storage = Storage::new(db_path)?;
+        let seed = random();
+        Ok(Actuator {
             function,
             height,
             round,
-            lock_round: None,
-            lock_votes: Vec::new(),
-            lock_proposal: None,
+            byzantine: byzantine_proposal(authority_list.len()),
+            reference: StateMachine::new(height, authority_list.clone()),
             authority_list,
             proposal: Vec::new(),
-            byzantine: byzantine_proposal(),
-            sleep_ms: 150,
-            storage: Storage::new(db_path),
-            vote_cache: VoteCache::new(),
-            proposal_cache: ProposalCache::new(),
+            commit_timeout: Duration::from_secs(1),
+            no_commit_window: Duration::from_millis(120),
+            recv_timeout: Duration::from_secs(5),
+            round_leap: false,
+            expect_lock: None,
+            suites: SuiteRegistry::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            storage,
+            storage_error: None,
             msg_cache: HashSet::new(),
             stime: Instant::now(),
             htime: Timespec::new(0, 0),
-        }
+        })
     }
 
-    /// A function to set a new authority list. Likewise it the new one should be with
-    /// length 4, and the first one should be the address of the testing node.
+    /// A function to set a new authority list. Likewise the first one should be the
+    /// address of the testing node, and it should have at least 2 nodes, or the tests
+    /// fail with `FrameError::IllegalScenario`.
     pub fn set_authority_list(&mut self, authority_list: Vec<Address>) {
+        self.byzantine = byzantine_proposal(authority_list.len());
+        self.reference.set_authority_list(authority_list.clone());
         self.authority_list = authority_list;
     }
 
-    /// A function to set a new sleep time as millisecond. The sleep time is the duration
-    /// time after send precommit votes before commit. If the testing node is wait for
-    /// more votes before commit when the framework get commit, it may return error of no
-    /// commit. Use this function to set a longer duration. The default duration is 150 milliseconds.
+    /// A function to set a new commit timeout as millisecond. It is the same as
+    /// `set_commit_timeout`, and is kept for compatibility.
     pub fn set_sleep_time(&mut self, ms: u64) {
-        self.sleep_ms = ms;
+        self.commit_timeout = Duration::from_millis(ms);
+    }
+
+    /// A function to set the commit timeout. After sending the precommit votes of a round
+    /// that should commit, the actuator waits for the commit until the timeout, and returns
+    /// `BftError::NoCommit` if the testing node does not commit. The default timeout is
+    /// 1 second.
+    pub fn set_commit_timeout(&mut self, timeout: Duration) {
+        self.commit_timeout = timeout;
+    }
+
+    /// A function to set the observation window of a round that should not commit. The
+    /// actuator watches the commit of the testing node during the window, and returns
+    /// `BftError::CommitInvalid` if it commits. The default window is 120 milliseconds.
+    pub fn set_no_commit_window(&mut self, window: Duration) {
+        self.no_commit_window = window;
+    }
+
+    /// A function to set the timeout of receiving a message from the testing node. If the
+    /// expected proposal or vote is not received in the timeout, the test fails with
+    /// `BftError::GetNoProposal` or `BftError::GetNoVote`. The default timeout is 5 seconds.
+    pub fn set_recv_timeout(&mut self, timeout: Duration) {
+        self.recv_timeout = timeout;
+    }
+
+    /// A function to set the seed of the actuator. All the random proposals of a test are
+    /// generated from it, and `all_test` generates the test cases with it, so the same
+    /// seed reproduces the same message sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// A function to get the seed of the actuator. The seed is logged when a test fails.
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
     /// A function to do whitebox testing with test cases input. The test cases are generated
-    /// in `test_case`.
+    /// in `test_case` or built by `Scenario::builder`, and the authority number of the test
+    /// cases should be the same as the length of authority list, or the test fails with
+    /// `FrameError::IllegalScenario`. A storage error stops the test with `BftError::Frame`.
     pub fn proc_test(&mut self, cases: BftTest) -> BftResult<()> {
+        self.proc_run(cases, None)?
+    }
+
+    // Do a test as a run in the storage, which is named by the suite if it has one.
+    fn proc_run(&mut self, cases: BftTest, suite: Option<&str>) -> FrameResult<BftResult<()>> {
+        if cases.authority_num() != self.authority_list.len() {
+            return Err(FrameError::IllegalScenario(format!(
+                "scenario of {:?} nodes mismatches the authority list of {:?} nodes",
+                cases.authority_num(),
+                self.authority_list.len()
+            )));
+        }
+        let res = self.run(suite, |actuator| actuator.proc_steps(&cases))?;
+        if let Err(ref err) = res {
+            error!("Test failed with seed {:?}: {:?}", self.seed, err);
+        }
+        Ok(res)
+    }
+
+    // Record the test done by `f` as a run, whose messages are stored with the run ID. The
+    // first storage error during the test is returned instead of the result of the test.
+    fn run<F>(&mut self, suite: Option<&str>, f: F) -> FrameResult<BftResult<()>>
+    where
+        F: FnOnce(&mut Self) -> BftResult<()>,
+    {
+        self.storage
+            .begin_run(self.seed, suite)
+            .map_err(storage_err)?;
+        self.storage_error = None;
         self.init();
-        for case in cases.iter() {
-            println!("{:?}", case);
-            if case == &SHOULD_COMMIT {
-                thread::sleep(::std::time::Duration::from_millis(self.sleep_ms));
-                if let Some(commit) = self.function.try_get_commit() {
-                    self.storage_msg(Msg::Commit(commit.clone()));
+        let res = f(self);
+        self.storage.finish_run(&res).map_err(storage_err)?;
+        match self.storage_error.take() {
+            Some(e) => Err(e),
+            None => Ok(res),
+        }
+    }
+
+    fn proc_steps(&mut self, cases: &BftTest) -> BftResult<()> {
+        for (index, case) in cases.iter().enumerate() {
+            self.storage.set_step(index);
+            self.proc_step(case)?;
+        }
+        info!(
+            "Test success, total test time: {:?}",
+            Instant::now() - self.stime
+        );
+        Ok(())
+    }
+
+    fn proc_step(&mut self, case: &Step) -> BftResult<()> {
+        debug!("Do step {:?}", case);
+        match case {
+            Step::ExpectCommit => {
+                let start = Instant::now();
+                if let Some(commit) = self.function.wait_commit(self.commit_timeout) {
+                    let latency = Instant::now() - start;
+                    debug!("Get commit after {:?}", latency);
+                    let res = self
+                        .storage
+                        .insert_latency(self.height, self.round, latency);
+                    self.storage_result(res);
+                    self.storage_msg(
+                        Msg::Commit(commit.clone()),
+                        Direction::Received,
+                        Origin::TestingNode,
+                    );
                     self.check_commit(commit)?;
                     let status = self.generate_status();
                     self.function.send(FrameSend::Status(status));
@@ -107,248 +243,383 @@ where
                 } else {
                     return Err(BftError::NoCommit(self.height));
                 }
-            } else if case == &NULL_ROUND {
+            }
+            Step::SkipRound => {
                 self.goto_next_round();
-            } else if case == &SHOULD_NOT_COMMIT {
-                thread::sleep(::std::time::Duration::from_millis(120));
-                if self.function.try_get_commit().is_some() {
+                self.round_leap = true;
+            }
+            Step::ExpectNoCommit => {
+                if let Some(commit) = self.function.wait_commit(self.no_commit_window) {
+                    // a commit of a previous height is a repeated one
+                    if commit.height < self.height {
+                        return Err(BftError::MultipleCommit(commit.height));
+                    }
                     return Err(BftError::CommitInvalid(self.height));
                 }
                 self.goto_next_round();
-            } else {
-                let prevote = case[0..3].to_vec();
-                let precommit = case[3..6].to_vec();
+            }
+            Step::Round(plan) => {
                 let proposer = self.function.cal_proposer(self.height, self.round);
+                let leap = self.round_leap;
+                self.round_leap = false;
 
-                if proposer == 0 {
+                if proposer == 0 && leap {
+                    // the testing node is in a previous round, it should jump to the round
+                    // by the prevotes of the round before it proposes
                     let feed = self.generate_feed();
-                    self.function.send(FrameSend::Feed(feed));
-                    self.check_proposal()?;
+                    self.proposal = self
+                        .reference
+                        .expect_proposal(self.authority_list[0].clone(), feed.proposal.clone())
+                        .content;
+                    self.generate_vote(VoteType::Prevote, &plan.prevote);
+                    self.function.send(FrameSend::Feed(feed.clone()));
+                    self.check_proposal(feed.proposal)?;
+                } else if proposer == 0 {
+                    let feed = self.generate_feed();
+                    self.function.send(FrameSend::Feed(feed.clone()));
+                    self.check_proposal(feed.proposal)?;
+                    self.generate_vote(VoteType::Prevote, &plan.prevote);
+                } else if proposer < self.authority_list.len() && leap {
+                    // likewise the testing node jumps by the prevotes before the proposal
+                    let proposal = self.generate_proposal(proposer);
+                    self.generate_vote(VoteType::Prevote, &plan.prevote);
+                    self.send_proposal(proposal);
                 } else if proposer < self.authority_list.len() {
-                    self.generate_proposal(proposer, self.lock_round, self.lock_votes.clone());
+                    let proposal = self.generate_proposal(proposer);
+                    self.send_proposal(proposal);
+                    self.generate_vote(VoteType::Prevote, &plan.prevote);
                 } else {
                     panic!("Proposer index beyond authority list!");
                 }
-
-                self.generate_prevote(prevote);
                 self.check_prevote()?;
-                self.generate_precommit(precommit);
+                self.generate_vote(VoteType::Precommit, &plan.precommit);
                 self.check_precommit()?;
             }
         }
-        info!(
-            "Test success, total test time: {:?}",
-            Instant::now() - self.stime
-        );
         Ok(())
     }
 
-    /// A function to do all whitebox tests.
+    /// A function to do whitebox testing with a scenario file. The authority list of the
+    /// actuator is replaced by the one in the file, and the initial height and round are
+    /// set if the file gives them. An illegal scenario file fails the test with
+    /// `FrameError::IllegalScenario`, and the actuator is left unchanged.
+    pub fn proc_scenario_file(&mut self, file: &ScenarioFile) -> BftResult<()> {
+        let cases = file.scenario()?;
+        self.set_authority_list(file.authority_list.clone());
+        if let Some(height) = file.height {
+            self.height = height;
+        }
+        if let Some(round) = file.round {
+            self.round = round;
+        }
+        info!("Do scenario {:?}", file.name);
+        self.proc_run(cases, Some(&file.name))?
+    }
+
+    /// A function to set the registry of the test suites run by `all_test` and
+    /// `run_suites`. The default registry has the built-in suites.
+    pub fn set_suite_registry(&mut self, registry: SuiteRegistry) {
+        self.suites = registry;
+    }
+
+    /// A function to do all whitebox tests in order. It stops at the first failed test
+    /// and returns the error.
     pub fn all_test(&mut self) -> BftResult<()> {
-        let all_test_cases = all_cases();
-        info!("Start all BFT test cases");
-        // info!("Do test test round leap");
-        // self.proc_test(round_leap_cases())?;
-        for (test_name, test_case) in all_test_cases.into_iter() {
-            info!("Do test {:?}", test_name);
-            let _ = self
-                .proc_test(test_case)
-                .map_err(|err| panic!("Error in test {:?}: {:?}", test_name, err));
+        self.run_suites(&SuiteConfig::default()).into_result()
+    }
+
+    /// A function to run the registered test suites selected by the `config` in order. The
+    /// testing node is reset by `Support::reset` after a failed suite if the rest suites
+    /// continue. Return the summary of the passed, the failed and the skipped suites.
+    pub fn run_suites(&mut self, config: &SuiteConfig) -> SuiteSummary {
+        let authority_num = self.authority_list.len();
+        let mut summary = SuiteSummary {
+            seed: self.seed,
+            results: Vec::new(),
+        };
+        let mut stop = false;
+        info!("Start BFT test suites with seed {:?}", self.seed);
+
+        for suite in self.suites.clone().suites().iter() {
+            let start = Instant::now();
+            let outcome = if stop || !config.is_selected(suite) {
+                SuiteOutcome::Skipped
+            } else {
+                info!("Do test suite {:?}: {}", suite.name(), suite.description());
+                let seed = self.seed;
+                match check_authority_num(authority_num)
+                    .and_then(|_| {
+                        self.proc_run(suite.generate(authority_num, seed), Some(suite.name()))
+                    })
+                    .unwrap_or_else(|e| Err(e.into()))
+                {
+                    Ok(()) => SuiteOutcome::Passed,
+                    Err(err) => {
+                        error!(
+                            "Error in test suite {:?} with seed {:?}: {:?}",
+                            suite.name(),
+                            self.seed,
+                            err
+                        );
+                        stop = !config.continue_on_failure;
+                        self.function.reset();
+                        self.recover();
+                        SuiteOutcome::Failed(err)
+                    }
+                }
+            };
+            let duration = Instant::now() - start;
+            if let Some(expected) = suite.expected_duration() {
+                if duration > expected {
+                    warn!(
+                        "Test suite {:?} takes {:?}, longer than {:?}",
+                        suite.name(),
+                        duration,
+                        expected
+                    );
+                }
+            }
+            summary.results.push(SuiteResult {
+                name: suite.name().to_string(),
+                tags: suite.tags().to_vec(),
+                outcome,
+                duration,
+            });
+        }
+        info!(
+            "BFT test suites finish, passed {:?}, failed {:?}, skipped {:?}",
+            summary.passed().len(),
+            summary.failed().len(),
+            summary.skipped().len()
+        );
+        summary
+    }
+
+    /// A function to do property-based testing. It generates random test cases with the
+    /// seed of the actuator and computes the expected outcome of each round by the quorum
+    /// rules. When the testing node fails a test case, the test case is shrunk to a
+    /// minimal failing one and returned as a `Counterexample`. An error of the framework,
+    /// such as an illegal authority list or a storage error, is returned as the outer
+    /// error instead.
+    pub fn property_test(
+        &mut self,
+        config: &PropertyConfig,
+    ) -> FrameResult<Result<(), Box<Counterexample>>> {
+        let authority_num = self.authority_list.len();
+        let seed = self.seed;
+        let mut gen = ScenarioGen::new(authority_num, config.max_rounds, seed);
+        info!("Start property test with seed {:?}", seed);
+
+        for case in 0..config.cases {
+            let plans = gen.generate_plans();
+            let cases = scenario_from_plans(authority_num, &plans)?;
+            self.recover();
+            if let Err(err) = self.proc_run(cases, None)? {
+                info!("Property test case {:?} failed, start shrinking", case);
+                let original_rounds = plans.len();
+                let (plans, error) = shrink(
+                    authority_num,
+                    plans,
+                    err,
+                    config.max_shrinks,
+                    |cases: &BftTest| {
+                        self.recover();
+                        self.proc_test(cases.clone()).err()
+                    },
+                );
+                self.recover();
+                let counterexample = Counterexample {
+                    case,
+                    seed,
+                    original_rounds,
+                    scenario: scenario_from_plans(authority_num, &plans)?,
+                    error,
+                };
+                error!("Property test failed: {:?}", counterexample);
+                return Ok(Err(Box::new(counterexample)));
+            }
+        }
+        info!("Property test pass {:?} cases", config.cases);
+        Ok(Ok(()))
+    }
+
+    /// A function to do bounded model checking. It enumerates all the sequences of up to
+    /// `max_rounds` rounds, and runs them one by one against the testing node, which is
+    /// reset by `Support::reset` before each run. The commit of every round is checked
+    /// with the expectation computed by the quorum rules, and so is the lock, which is
+    /// observed by the proposal or the prevote of the testing node in the next round. It
+    /// stops at the first counterexample and returns the report of the coverage. An error
+    /// of the framework, such as an illegal authority list or a storage error, is
+    /// returned instead of the report.
+    pub fn model_check(&mut self, config: &ModelCheckConfig) -> FrameResult<ModelCheckReport> {
+        let authority_num = self.authority_list.len();
+        check_authority_num(authority_num)?;
+        let checker = ModelChecker::new(authority_num, config);
+        let mut report = ModelCheckReport {
+            round_plans: checker.plans().len(),
+            total: checker.total(),
+            ..ModelCheckReport::default()
+        };
+        info!(
+            "Start model checking {:?} sequences of {:?} round plans",
+            report.total, report.round_plans
+        );
+
+        for (index, plans) in checker.enumerate() {
+            if config
+                .max_runs
+                .map(|max| report.runs >= max)
+                .unwrap_or(false)
+            {
+                break;
+            }
+            let expects = expect_rounds(&plans);
+            self.function.reset();
+            self.recover();
+            report.record(&expects);
+            let res = self.run(None, |actuator| actuator.proc_model(&plans, &expects))?;
+            if let Err(error) = res {
+                let violation = Violation {
+                    index: index as u64,
+                    scenario: scenario_from_plans(authority_num, &plans)?,
+                    expects,
+                    error,
+                };
+                error!("Model checking failed: {:?}", violation);
+                report.violation = Some(violation);
+                break;
+            }
+            report.passed += 1;
+        }
+        info!(
+            "Model checking run {:?} of {:?} sequences, coverage {:.2}%",
+            report.runs,
+            report.total,
+            report.coverage() * 100.0
+        );
+        Ok(report)
+    }
+
+    fn proc_model(&mut self, plans: &[RoundPlan], expects: &[RoundExpect]) -> BftResult<()> {
+        let mut lock = false;
+        for (index, (plan, expect)) in plans.iter().zip(expects.iter()).enumerate() {
+            self.storage.set_step(index);
+            // the lock at the end of the previous round is observed in this round
+            self.expect_lock = Some(lock);
+            let res = self.proc_step(&Step::Round(plan.clone()));
+            self.expect_lock = None;
+            res?;
+            lock = expect.lock && !expect.commit;
+            if expect.commit {
+                self.proc_step(&Step::ExpectCommit)?;
+            } else {
+                self.proc_step(&Step::ExpectNoCommit)?;
+            }
         }
-        info!("All BFT test cases pass");
         Ok(())
     }
 
-    fn generate_feed(&self) -> Feed {
-        let mut proposal = vec![0, 0, 0, 0, 0, 0];
-        while self.byzantine.contains(&proposal) {
-            let mut rng = thread_rng();
+    // Generate a random proposal content which is not a byzantine one.
+    fn random_proposal(&mut self) -> Vec<u8> {
+        let mut proposal = vec![0; 6];
+        loop {
             for ii in proposal.iter_mut() {
-                *ii = rng.gen();
+                *ii = self.rng.gen();
+            }
+            if !self.byzantine.contains(&proposal) {
+                return proposal;
             }
         }
+    }
+
+    fn generate_feed(&mut self) -> Feed {
+        let proposal = self.random_proposal();
         let res = Feed {
             height: self.height,
             proposal,
         };
-        self.storage_msg(Msg::Feed(res.clone()));
+        self.storage_msg(Msg::Feed(res.clone()), Direction::Sent, Origin::Framework);
         res
     }
 
-    fn generate_status(&self) -> Status {
+    fn generate_status(&mut self) -> Status {
         let res = Status {
             height: self.height,
             authority_list: self.authority_list.clone(),
         };
-        self.storage_msg(Msg::Status(res.clone()));
+        self.storage_msg(Msg::Status(res.clone()), Direction::Sent, Origin::Framework);
         res
     }
 
-    fn generate_proposal(
-        &mut self,
-        auth_index: usize,
-        lock_round: Option<u64>,
-        lock_votes: Vec<Vote>,
-    ) {
-        let proposal = if self.lock_proposal.is_some() {
-            self.lock_proposal.clone().unwrap()
-        } else {
-            let mut tmp = vec![0, 0, 0, 0, 0, 0];
-            while self.byzantine.contains(&tmp) {
-                let mut rng = thread_rng();
-                for ii in tmp.iter_mut() {
-                    *ii = rng.gen();
-                }
-            }
-            tmp
-        };
-        self.proposal = proposal.clone();
+    fn generate_proposal(&mut self, auth_index: usize) -> Proposal {
+        let tmp = self.random_proposal();
+        // the simulated proposer proposes as a correct node
+        let proposal = self
+            .reference
+            .expect_proposal(self.authority_list[auth_index].clone(), tmp);
+        self.proposal = proposal.content.clone();
+        self.reference.add_proposal(proposal.clone());
+        proposal
+    }
 
-        let proposal = Proposal {
-            height: self.height,
-            round: self.round,
-            content: proposal,
-            proposer: self.authority_list[auth_index].clone(),
-            lock_round,
-            lock_votes,
-        };
-        self.proposal_cache.add(proposal.clone());
-        self.storage_msg(Msg::Proposal(proposal.clone()));
+    fn send_proposal(&mut self, proposal: Proposal) {
+        self.storage_msg(
+            Msg::Proposal(proposal.clone()),
+            Direction::Sent,
+            Origin::HonestPeer,
+        );
         self.function.send(FrameSend::Proposal(proposal.clone()));
         debug!("Send proposal {:?}", proposal);
     }
 
-    fn generate_prevote(&mut self, prevote: Vec<u8>) {
-        let proposal = if self.lock_proposal.is_none() {
-            self.proposal.clone()
-        } else {
-            self.lock_proposal.clone().unwrap()
-        };
-
-        for (i, item) in prevote.iter().enumerate().take(3) {
-            if *item == NORMAL {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Prevote,
-                    proposal: proposal.clone(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send normal prevote {:?}", vote);
-            } else if *item == BYZANTINE {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Prevote,
-                    proposal: self.byzantine[i].clone(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send byzantine prevote {:?}", vote);
-            } else if *item == NIL {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Prevote,
-                    proposal: Vec::new(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send nil prevote {:?}", vote);
-            }
-        }
-    }
-
-    fn generate_precommit(&mut self, precommit: Vec<u8>) {
-        let proposal = if self.lock_proposal.is_none() {
-            self.proposal.clone()
-        } else {
-            self.lock_proposal.clone().unwrap()
+    fn generate_vote(&mut self, vote_type: VoteType, behaviors: &[Behavior]) {
+        let proposal = match self.reference.lock() {
+            Some(lock) => lock.proposal.clone(),
+            None => self.proposal.clone(),
         };
 
-        for (i, item) in precommit.iter().enumerate().take(3) {
-            if *item == NORMAL {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Precommit,
-                    proposal: proposal.clone(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send normal precommit {:?}", vote);
-            } else if *item == BYZANTINE {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Precommit,
-                    proposal: self.byzantine[i].clone(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send byzantine precommit {:?}", vote);
-            } else if *item == NIL {
-                let vote = Vote {
-                    height: self.height,
-                    round: self.round,
-                    vote_type: VoteType::Precommit,
-                    proposal: Vec::new(),
-                    voter: self.authority_list[i + 1].clone(),
-                };
-
-                self.storage_msg(Msg::Vote(vote.clone()));
-                self.function.send(FrameSend::Vote(vote.clone()));
-                self.vote_cache.add(vote.clone());
-                debug!("Send nil precommit {:?}", vote);
-            }
+        for (i, behavior) in behaviors.iter().enumerate() {
+            let (vote_proposal, origin) = match behavior {
+                Behavior::Normal => (proposal.clone(), Origin::HonestPeer),
+                Behavior::Byzantine => (self.byzantine[i].clone(), Origin::ByzantinePeer),
+                Behavior::Nil => (Vec::new(), Origin::HonestPeer),
+                Behavior::Offline => continue,
+            };
+            let vote = Vote {
+                height: self.height,
+                round: self.round,
+                vote_type: vote_type.clone(),
+                proposal: vote_proposal,
+                voter: self.authority_list[i + 1].clone(),
+            };
+
+            self.storage_msg(Msg::Vote(vote.clone()), Direction::Sent, origin);
+            self.function.send(FrameSend::Vote(vote.clone()));
+            self.reference.add_vote(vote.clone());
+            debug!("Send {:?} {:?} {:?}", behavior, vote_type, vote);
         }
     }
 
     fn check_prevote(&mut self) -> BftResult<()> {
-        let vote = self.receive_vote(VoteType::Prevote)?;
         debug!(
             "Check prevote at height {:?}, round {:?}",
             self.height, self.round
         );
-        let mut clean_flag = true;
-
-        if let Some(prevote_set) =
-            self.vote_cache
-                .get_voteset(self.height, self.round, VoteType::Prevote)
-        {
-            // check prevote condition
-            for (p, count) in prevote_set.votes_by_proposal {
-                if self.is_above_threshold(count).is_ok() {
-                    clean_flag = false;
-                    if !p.is_empty() {
-                        self.set_polc(p);
-                    } else {
-                        self.clean_polc();
-                    }
-                }
+        // the prevote only depends on the proposal and the lock before the prevotes
+        let expect = self.reference.expect_prevote();
+        let vote = self.receive_vote(VoteType::Prevote)?;
+        // a nil prevote for a new proposal of others shows the testing node is locked
+        if let Some(p) = self.reference.proposal() {
+            if p.proposer != self.authority_list[0] && p.lock_round.is_none() {
+                self.observe_lock(vote.proposal.is_empty())?;
             }
-        } else {
-            return Err(BftError::IllegalVote(vote));
         }
-        if clean_flag {
-            self.proposal = Vec::new();
+        if vote.proposal != expect {
+            return Err(BftError::IllegalVote(vote));
         }
+
+        self.reference.handle_prevotes();
+        self.proposal = self.reference.polka().unwrap_or_default();
         Ok(())
     }
 
@@ -359,34 +630,18 @@ where
             self.height, self.round
         );
 
-        if let Some(prevote_set) =
-            self.vote_cache
-                .get_voteset(self.height, self.round, VoteType::Prevote)
-        {
-            // check precommit condition
-            self.is_above_threshold(prevote_set.count)?;
-            for (p, count) in prevote_set.votes_by_proposal.iter() {
-                if self.is_above_threshold(*count).is_ok() {
-                    if p != &vote.proposal {
-                        return Err(BftError::PrecommitErr(self.height, self.round));
-                    }
-
-                    let polc = prevote_set.extract_polc(
-                        self.height,
-                        self.round,
-                        VoteType::Prevote,
-                        &vote.proposal.clone(),
-                    );
-                    if polc.len() < 3 {
-                        return Err(BftError::PrecommitDiffPoLC(self.height, self.round));
-                    }
-                    self.lock_votes = polc;
-                }
+        // a precommit for a proposal without +2/3 prevotes for it is never allowed
+        let polka = self.reference.polka();
+        match self.reference.expect_precommit() {
+            None => Err(BftError::ShouldNotPrecommit(self.height, self.round)),
+            Some(_) if !vote.proposal.is_empty() && polka.as_ref() != Some(&vote.proposal) => {
+                Err(BftError::ShouldNotPrecommit(self.height, self.round))
             }
-        } else {
-            return Err(BftError::IllegalVote(vote));
+            Some(ref p) if p != &vote.proposal => {
+                Err(BftError::PrecommitErr(self.height, self.round))
+            }
+            Some(_) => Ok(()),
         }
-        Ok(())
     }
 
     fn check_commit(&mut self, commit: Commit) -> BftResult<()> {
@@ -395,147 +650,268 @@ where
             self.height, self.round
         );
 
+        if commit.height < self.height {
+            return Err(BftError::MultipleCommit(commit.height));
+        }
+        if commit.height > self.height {
+            return Err(BftError::MislaidCommit(commit.height));
+        }
         if self.byzantine.contains(&commit.result)
-            || self.lock_round.is_none()
-            || self.proposal != commit.result
-            || self
-                .proposal_cache
-                .get_proposal(self.height, self.round)
-                .unwrap()
-                .content
-                != commit.result
+            || self.reference.expect_commit() != Some(commit.result)
         {
             return Err(BftError::CommitIncorrect(self.height));
         }
-
-        if let Some(precommit_set) =
-            self.vote_cache
-                .get_voteset(self.height, self.round, VoteType::Precommit)
-        {
-            if precommit_set
-                .extract_polc(self.height, self.round, VoteType::Precommit, &commit.result)
-                .len()
-                < 3
-            {
-                return Err(BftError::CommitIncorrect(self.height));
-            }
-        }
         Ok(())
     }
 
-    fn check_proposal(&mut self) -> BftResult<()> {
+    fn check_proposal(&mut self, feed: Vec<u8>) -> BftResult<()> {
         info!(
             "Check proposal at height {:?}, round{:?}",
             self.height, self.round
         );
 
-        let mut msg;
-        loop {
-            let tmp = self.function.recv();
-            if !self.msg_cache.contains(&tmp) {
-                msg = tmp;
-                break;
-            }
+        let p = match self.recv_msg() {
+            Some(FrameRecv::Proposal(p)) => p,
+            Some(_) => return Err(BftError::IllegalProposal(self.height, self.round)),
+            None => return Err(BftError::GetNoProposal(self.height, self.round)),
+        };
+        // a proposal with a lock round shows the testing node is locked
+        self.observe_lock(p.lock_round.is_some())?;
+        if !self.reference.is_expected_proposal(&p, feed) {
+            return Err(BftError::IllegalProposal(self.height, self.round));
         }
-        self.msg_cache.insert(msg.clone());
+        self.reference.add_proposal(p.clone());
+        self.proposal = p.content;
+        Ok(())
+    }
 
-        match msg {
-            FrameRecv::Proposal(p) => Ok(p),
-            _ => Err(BftError::IllegalProposal(self.height, self.round)),
+    // Compare the lock of the testing node observed from its messages with the one
+    // expected by model checking.
+    fn observe_lock(&self, locked: bool) -> BftResult<()> {
+        match self.expect_lock {
+            Some(lock) if lock != locked => Err(BftError::LockDiff(self.height, self.round)),
+            _ => Ok(()),
         }
-        .and_then(|p| {
-            if self.lock_round.is_some() {
-                if p.lock_round.is_none() || Some(p.content.clone()) != self.lock_proposal {
-                    return Err(BftError::IllegalProposal(self.height, self.round));
-                }
-            } else if p.lock_round.is_some() {
-                return Err(BftError::IllegalProposal(self.height, self.round));
-            }
-            self.proposal_cache.add(p.clone());
-            self.proposal = p.content;
-            Ok(())
-        })
     }
 
     fn receive_vote(&mut self, vote_type: VoteType) -> BftResult<Vote> {
-        let mut msg;
-        let mut vote;
-        loop {
-            let tmp = self.function.recv();
-            if !self.msg_cache.contains(&tmp) {
-                msg = tmp;
-                break;
-            }
-        }
-        self.msg_cache.insert(msg.clone());
-
-        match msg {
-            FrameRecv::Proposal(p) => return Err(BftError::AbnormalProposal(p)),
-            FrameRecv::Vote(v) => vote = v,
-        }
+        let vote = match self.recv_msg() {
+            Some(FrameRecv::Proposal(p)) => return Err(BftError::AbnormalProposal(p)),
+            Some(FrameRecv::Vote(v)) => v,
+            None => return Err(BftError::GetNoVote(self.height, self.round, vote_type)),
+        };
 
         if vote.vote_type != vote_type || self.byzantine.contains(&vote.proposal) {
             // check vote type and vote proposal
             return Err(BftError::IllegalVote(vote));
         }
-        self.vote_cache.add(vote.clone());
-        self.storage_msg(Msg::Vote(vote.clone()));
+        self.reference.add_vote(vote.clone());
         debug!("Receive vote {:?}", vote.clone());
         Ok(vote)
     }
 
-    fn is_above_threshold(&self, num: usize) -> BftResult<()> {
-        if num * 3 <= self.authority_list.len() * 2 {
-            return Err(BftError::ShouldNotPrecommit(self.height, self.round));
+    fn recv_msg(&mut self) -> Option<FrameRecv> {
+        // skip the repeated messages and the messages of previous heights
+        let deadline = Instant::now() + self.recv_timeout;
+        loop {
+            let now = Instant::now();
+            if now > deadline {
+                return None;
+            }
+            let msg = self.function.recv_timeout(deadline - now)?;
+            let height = match msg {
+                FrameRecv::Proposal(ref p) => p.height,
+                FrameRecv::Vote(ref v) => v.height,
+            };
+            if height >= self.height && !self.msg_cache.contains(&msg) {
+                self.msg_cache.insert(msg.clone());
+                let record = match msg {
+                    FrameRecv::Proposal(ref p) => Msg::Proposal(p.clone()),
+                    FrameRecv::Vote(ref v) => Msg::Vote(v.clone()),
+                };
+                self.storage_msg(record, Direction::Received, Origin::TestingNode);
+                return Some(msg);
+            }
         }
-        Ok(())
     }
 
-    fn set_polc(&mut self, proposal: Vec<u8>) {
-        self.proposal = proposal.clone();
-        self.lock_round = Some(self.round);
-        self.lock_proposal = Some(proposal);
+    fn storage_msg(&mut self, msg: Msg, direction: Direction, origin: Origin) {
+        let res = self.storage.insert(msg, direction, origin);
+        self.storage_result(res);
     }
 
-    fn clean_polc(&mut self) {
-        self.proposal = Vec::new();
-        self.lock_round = None;
-        self.lock_votes.clear();
-        self.lock_proposal = None;
-    }
-
-    fn storage_msg(&self, msg: Msg) {
-        let res = self.storage.insert(msg.clone());
-        if res.is_err() {
-            panic!("SQLite Error {:?} when insert {:?}", res, msg);
+    // Keep the first storage error of a run, which is returned when the run finishes.
+    fn storage_result<R>(&mut self, res: rusqlite::Result<R>) {
+        if let Err(e) = res {
+            error!("SQLite Error {:?}", e);
+            if self.storage_error.is_none() {
+                self.storage_error = Some(storage_err(e));
+            }
         }
     }
 
     fn goto_next_height(&mut self) {
-        self.vote_cache.clear_prevote_count();
         self.msg_cache.clear();
-        self.clean_polc();
+        self.proposal = Vec::new();
         self.round = 0;
+        self.round_leap = false;
         self.height += 1;
+        self.reference.goto_height(self.height);
         self.htime = time::get_time();
         info!("Go to next height");
     }
 
     fn goto_next_round(&mut self) {
-        if self.lock_round.is_none() {
-            self.proposal = Vec::new();
-        } else {
-            self.proposal = self.lock_proposal.clone().unwrap();
-        }
+        self.proposal = self
+            .reference
+            .lock()
+            .map(|lock| lock.proposal.clone())
+            .unwrap_or_default();
         self.round += 1;
+        self.reference.goto_round(self.round);
+    }
+
+    /// Clean the states of the current height after a failed test, so that the next
+    /// test can start from a new height.
+    fn recover(&mut self) {
+        while self.function.try_get_commit().is_some() {}
+        self.msg_cache.clear();
+        self.proposal = Vec::new();
+        self.round = 0;
+        self.round_leap = false;
     }
 
     fn init(&mut self) {
-        info!("Init a unit test");
+        info!("Init a unit test with seed {:?}", self.seed);
+        self.rng = StdRng::seed_from_u64(self.seed);
         let gensis = self.generate_status();
         self.height += 1;
-        self.storage_msg(Msg::Status(gensis.clone()));
+        self.reference.goto_height(self.height);
+        self.reference.goto_round(self.round);
         self.function.send(FrameSend::Status(gensis));
         self.htime = time::get_time();
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::*;
+    use crate::whitebox::{
+        collection::storage::TempDb,
+        correctness::scenario::Scenario,
+        mock::{Fault, MockNode},
+    };
+
+    fn model_check(node: MockNode, name: &str) -> BftResult<()> {
+        let db = TempDb::new(name);
+        // the testing node proposes in the round 1 of the height 3
+        let authority_list = (0..4).map(|i| vec![i]).collect();
+        let mut actuator = Actuator::new(node, 2, 0, authority_list, db.path());
+        actuator.set_no_commit_window(Duration::from_millis(1));
+        let plans = vec![
+            RoundPlan::new(
+                vec![Behavior::Normal; 3],
+                vec![Behavior::Normal, Behavior::Offline, Behavior::Offline],
+            ),
+            RoundPlan::all(4, Behavior::Normal, Behavior::Normal),
+        ];
+        let expects = expect_rounds(&plans);
+        actuator
+            .run(None, |a| a.proc_model(&plans, &expects))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_storage_error() {
+        let db = TempDb::new("actuator_version");
+        rusqlite::Connection::open(db.path())
+            .unwrap()
+            .execute_batch("PRAGMA user_version = 99")
+            .unwrap();
+        let authority_list = (0..4).map(|i| vec![i]).collect();
+        match Actuator::try_new(MockNode::new(vec![0]), 0, 0, authority_list, db.path()) {
+            Err(FrameError::Storage(_)) => (),
+            Err(e) => panic!("Get {:?}", e),
+            Ok(_) => panic!("Open a database of another version"),
+        }
+    }
+
+    #[test]
+    fn test_scenario_mismatch() {
+        let db = TempDb::new("actuator_mismatch");
+        let authority_list = (0..4).map(|i| vec![i]).collect();
+        let mut actuator = Actuator::new(MockNode::new(vec![0]), 0, 0, authority_list, db.path());
+        let cases = Scenario::builder(7)
+            .plan(RoundPlan::all(7, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .unwrap();
+        match actuator.proc_test(cases) {
+            Err(BftError::Frame(FrameError::IllegalScenario(_))) => (),
+            res => panic!("Get {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_illegal_scenario_file() {
+        let db = TempDb::new("actuator_scenario_file");
+        let authority_list: Vec<Address> = (0..4).map(|i| vec![i]).collect();
+        let mut actuator = Actuator::new(
+            MockNode::new(vec![0]),
+            0,
+            0,
+            authority_list.clone(),
+            db.path(),
+        );
+        // a round without expectation
+        let file = ScenarioFile {
+            name: "illegal".to_string(),
+            description: String::new(),
+            authority_list,
+            height: None,
+            round: None,
+            steps: vec![Step::Round(RoundPlan::all(
+                4,
+                Behavior::Normal,
+                Behavior::Normal,
+            ))],
+        };
+        match actuator.proc_scenario_file(&file) {
+            Err(BftError::Frame(FrameError::IllegalScenario(_))) => (),
+            res => panic!("Get {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_authority_num() {
+        let db = TempDb::new("actuator_authority");
+        match Actuator::try_new(MockNode::new(vec![0]), 0, 0, vec![vec![0]], db.path()) {
+            Err(FrameError::IllegalScenario(_)) => (),
+            Err(e) => panic!("Get {:?}", e),
+            Ok(_) => panic!("Create an actuator of one node"),
+        }
+
+        let mut actuator = Actuator::new(
+            MockNode::new(vec![0]),
+            0,
+            0,
+            vec![vec![0], vec![1]],
+            db.path(),
+        );
+        actuator.set_authority_list(vec![vec![0]]);
+        match actuator.all_test() {
+            Err(BftError::Frame(FrameError::IllegalScenario(_))) => (),
+            res => panic!("Get {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_observe_lock() {
+        model_check(MockNode::new(vec![0]), "observe_lock").unwrap();
+        // the node proposes without a lock round after a PoLC
+        match model_check(MockNode::faulty(vec![0], Fault::IgnoreLock), "ignore_lock") {
+            Err(BftError::LockDiff(3, 1)) => (),
+            res => panic!("Lock is not observed, get {:?}", res),
+        }
+    }
+}
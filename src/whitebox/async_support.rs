@@ -0,0 +1,222 @@
+use crate::whitebox::types::{Commit, FrameRecv, FrameSend, Support};
+
+use futures::{
+    executor::block_on,
+    future::{self, BoxFuture},
+    task::{waker, ArcWake},
+};
+
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Whitebox test support for a futures-based testing node. Wrap it with `AsyncBridge`
+/// to test it by the actuator.
+///
+/// The futures are polled outside any runtime, so they must not depend on the context
+/// of a runtime, such as the timers or the sockets of tokio, which panic or never wake
+/// there. A testing node running on a runtime should keep its tasks on the runtime and
+/// return the runtime-free futures of the `futures::channel` that connect to the tasks.
+pub trait AsyncSupport: Send + Sync + 'static {
+    /// Send a `FrameSend` message to the testing node.
+    fn send(&self, msg: FrameSend) -> BoxFuture<'_, ()>;
+    /// Receive a `FrameRecv` message from the testing node. The future is dropped if it
+    /// is not ready before the timeout, so it should be cancel safe.
+    fn recv(&self) -> BoxFuture<'_, FrameRecv>;
+    /// Get a commit message from the testing node. The future is dropped if the testing
+    /// node does not commit before the timeout, so it should be cancel safe.
+    fn commit(&self) -> BoxFuture<'_, Commit>;
+    /// Stop the testing node.
+    fn stop(&self) -> BoxFuture<'_, ()>;
+    /// Determine the proposer index in the authority list by
+    /// the given height and round.
+    fn cal_proposer(&self, height: u64, round: u64) -> usize;
+    /// Reset the testing node to a clean state. The default implementation does nothing.
+    fn reset(&self) -> BoxFuture<'_, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+/// A bridge to test an `AsyncSupport` testing node by the actuator. It drives the futures
+/// on the thread of the actuator without a runtime, and drops a future when it is timeout.
+/// Only the runtime-free futures are supported, see `AsyncSupport`.
+#[derive(Debug)]
+pub struct AsyncBridge<T> {
+    inner: Arc<T>,
+}
+
+impl<T> AsyncBridge<T>
+where
+    T: AsyncSupport,
+{
+    /// A function to create a bridge of the `AsyncSupport` testing node.
+    pub fn new(inner: T) -> Self {
+        AsyncBridge {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// A function to get the `AsyncSupport` testing node.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Clone for AsyncBridge<T> {
+    fn clone(&self) -> Self {
+        AsyncBridge {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Support for AsyncBridge<T>
+where
+    T: AsyncSupport,
+{
+    fn send(&self, msg: FrameSend) {
+        block_on(self.inner.send(msg));
+    }
+
+    fn recv(&self) -> FrameRecv {
+        block_on(self.inner.recv())
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Option<FrameRecv> {
+        block_on_timeout(self.inner.recv(), timeout)
+    }
+
+    fn try_get_commit(&self) -> Option<Commit> {
+        block_on_timeout(self.inner.commit(), Duration::from_secs(0))
+    }
+
+    fn wait_commit(&self, timeout: Duration) -> Option<Commit> {
+        block_on_timeout(self.inner.commit(), timeout)
+    }
+
+    fn stop(&self) {
+        block_on(self.inner.stop());
+    }
+
+    fn cal_proposer(&self, height: u64, round: u64) -> usize {
+        self.inner.cal_proposer(height, round)
+    }
+
+    fn reset(&self) {
+        block_on(self.inner.reset());
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl ArcWake for ThreadWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+/// A function to run a future on the current thread until it is ready or the `timeout`.
+/// The future is polled at least once, and it is dropped when it is timeout, which
+/// cancels it. Return `None` if it is timeout. No runtime is entered to poll the future.
+pub fn block_on_timeout<F: Future>(fut: F, timeout: Duration) -> Option<F::Output> {
+    let mut fut = Box::pin(fut);
+    let waker = waker(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+            return Some(res);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::channel::oneshot;
+
+    #[test]
+    fn test_block_on_timeout() {
+        let (sender, receiver) = oneshot::channel::<u8>();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            sender.send(1).unwrap();
+        });
+        assert_eq!(
+            block_on_timeout(receiver, Duration::from_secs(5)),
+            Some(Ok(1))
+        );
+
+        let (_sender, receiver) = oneshot::channel::<u8>();
+        let start = Instant::now();
+        assert_eq!(block_on_timeout(receiver, Duration::from_millis(20)), None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(
+            block_on_timeout(future::ready(2), Duration::from_secs(0)),
+            Some(2)
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_async_bridge() {
+        use crate::whitebox::{
+            actuator::Actuator, collection::storage::TempDb, mock::MockNode, types::Address,
+        };
+        use futures::future::poll_fn;
+
+        // an async testing node that wraps the mock node
+        struct AsyncMock(MockNode);
+
+        impl AsyncSupport for AsyncMock {
+            fn send(&self, msg: FrameSend) -> BoxFuture<'_, ()> {
+                Support::send(&self.0, msg);
+                Box::pin(future::ready(()))
+            }
+
+            fn recv(&self) -> BoxFuture<'_, FrameRecv> {
+                Box::pin(poll_fn(move |_| {
+                    match self.0.recv_timeout(Duration::from_secs(0)) {
+                        Some(msg) => Poll::Ready(msg),
+                        None => Poll::Pending,
+                    }
+                }))
+            }
+
+            fn commit(&self) -> BoxFuture<'_, Commit> {
+                Box::pin(poll_fn(move |_| match self.0.try_get_commit() {
+                    Some(commit) => Poll::Ready(commit),
+                    None => Poll::Pending,
+                }))
+            }
+
+            fn stop(&self) -> BoxFuture<'_, ()> {
+                Support::stop(&self.0);
+                Box::pin(future::ready(()))
+            }
+
+            fn cal_proposer(&self, height: u64, round: u64) -> usize {
+                self.0.cal_proposer(height, round)
+            }
+        }
+
+        let db = TempDb::new("async_bridge");
+        let authority_list: Vec<Address> = (0..4).map(|i| vec![i]).collect();
+        let mut actuator = Actuator::new(
+            AsyncBridge::new(AsyncMock(MockNode::new(vec![0]))),
+            0,
+            0,
+            authority_list,
+            db.path(),
+        );
+        actuator.set_no_commit_window(Duration::from_millis(1));
+        actuator.all_test().unwrap();
+    }
+}
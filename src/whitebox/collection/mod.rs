@@ -1,8 +1,12 @@
 ///
 pub mod proposal_cache;
 ///
+pub mod safety;
+///
 pub mod storage;
 ///
+pub mod trace;
+///
 pub mod util;
 ///
 pub mod vote_cache;
@@ -0,0 +1,481 @@
+use crate::whitebox::{
+    collection::{
+        trace::{Record, TraceReader},
+        util::{Msg, Origin},
+    },
+    reference::{can_prevote, LockChange},
+    types::{Commit, FrameResult, Status, Vote, VoteType},
+};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A violation of the BFT safety found in a trace. The `seqs` are the sequence numbers of
+/// the messages which violate the safety.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// Two different commits at the same height.
+    ConflictingCommit {
+        /// The height of the commits.
+        height: u64,
+        /// The sequence numbers of the two commits.
+        seqs: Vec<u64>,
+    },
+    /// A commit without +2/3 precommits of the committed proposal in any round.
+    UnbackedCommit {
+        /// The height of the commit.
+        height: u64,
+        /// The sequence number of the commit.
+        seqs: Vec<u64>,
+    },
+    /// A commit whose authority list is unknown, since no status before it is recorded.
+    NoAuthorityList {
+        /// The height of the commit.
+        height: u64,
+        /// The sequence number of the commit.
+        seqs: Vec<u64>,
+    },
+    /// A validator votes for two different proposals in the same round.
+    DoubleVote {
+        /// The address of the validator.
+        voter: Vec<u8>,
+        /// The height of the votes.
+        height: u64,
+        /// The round of the votes.
+        round: u64,
+        /// The type of the votes.
+        vote_type: VoteType,
+        /// The sequence numbers of the two votes.
+        seqs: Vec<u64>,
+    },
+    /// A validator locked on a proposal prevotes for another one in a later round, while
+    /// there are neither +2/3 prevotes of the other one nor +2/3 nil prevotes after the
+    /// lock round.
+    LockViolation {
+        /// The address of the validator.
+        voter: Vec<u8>,
+        /// The height of the votes.
+        height: u64,
+        /// The round of the prevote.
+        round: u64,
+        /// The sequence numbers of the precommit of the lock and the prevote.
+        seqs: Vec<u64>,
+    },
+}
+
+impl Violation {
+    /// A function to get the sequence numbers of the messages of the violation.
+    pub fn seqs(&self) -> &[u64] {
+        match self {
+            Violation::ConflictingCommit { seqs, .. }
+            | Violation::UnbackedCommit { seqs, .. }
+            | Violation::NoAuthorityList { seqs, .. }
+            | Violation::DoubleVote { seqs, .. }
+            | Violation::LockViolation { seqs, .. } => seqs,
+        }
+    }
+}
+
+/// The safety report of a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SafetyReport {
+    /// The ID of the run.
+    pub run_id: u64,
+    /// The violations in order of the checks.
+    pub violations: Vec<Violation>,
+}
+
+impl SafetyReport {
+    /// A function to check whether the run is safe.
+    pub fn is_safe(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// A function to check the safety of all the runs of a database recorded by the actuator.
+pub fn check_trace(path: &str) -> FrameResult<Vec<SafetyReport>> {
+    let reader = TraceReader::open(path)?;
+    reader
+        .runs()?
+        .iter()
+        .map(|run| check_run(&reader, run.run_id))
+        .collect()
+}
+
+/// A function to check the safety of a run. The checks are:
+///
+/// 1. No two different commits at one height.
+/// 2. Every commit is backed by +2/3 precommits of the authorities in a round.
+/// 3. No validator votes for two different proposals in one round.
+/// 4. No validator which is not a byzantine peer prevotes for a proposal other than its
+///    lock, unless there are +2/3 prevotes of the proposal after the lock round, or it has
+///    unlocked by +2/3 nil prevotes after the lock round.
+///
+/// The authority list of a height is the one of the last status below the height.
+pub fn check_run(reader: &TraceReader, run_id: u64) -> FrameResult<SafetyReport> {
+    let mut statuses = Vec::new();
+    let mut votes = Vec::new();
+    let mut commits = Vec::new();
+    for record in reader.messages(run_id)?.into_iter() {
+        match record.msg.clone() {
+            Msg::Status(s) => statuses.push(record.map(|_| s)),
+            Msg::Vote(v) => votes.push(record.map(|_| v)),
+            Msg::Commit(c) => commits.push(record.map(|_| c)),
+            Msg::Proposal(_) | Msg::Feed(_) => (),
+        }
+    }
+
+    let trace = Trace { statuses, votes };
+    let mut violations = Vec::new();
+    check_commits(&trace, &commits, &mut violations);
+    check_double_votes(&trace, &mut violations);
+    check_locks(&trace, &mut violations);
+    Ok(SafetyReport { run_id, violations })
+}
+
+// The lock round, the lock proposal and the sequence number of the precommit.
+type Lock<'a> = (u64, &'a Vec<u8>, u64);
+
+struct Trace {
+    statuses: Vec<Record<Status>>,
+    votes: Vec<Record<Vote>>,
+}
+
+impl Trace {
+    fn authority_list(&self, height: u64) -> Option<&[Vec<u8>]> {
+        self.statuses
+            .iter()
+            .rev()
+            .find(|s| s.msg.height < height)
+            .map(|s| s.msg.authority_list.as_slice())
+    }
+
+    // Whether there are +2/3 votes of the `proposal` in the round.
+    fn is_quorum(
+        &self,
+        authority_list: &[Vec<u8>],
+        height: u64,
+        round: u64,
+        vote_type: &VoteType,
+        proposal: &[u8],
+    ) -> bool {
+        let voters = self
+            .votes
+            .iter()
+            .map(|v| &v.msg)
+            .filter(|v| {
+                v.height == height
+                    && v.round == round
+                    && &v.vote_type == vote_type
+                    && v.proposal.as_slice() == proposal
+                    && authority_list.contains(&v.voter)
+            })
+            .map(|v| &v.voter)
+            .collect::<HashSet<_>>();
+        voters.len() * 3 > authority_list.len() * 2
+    }
+
+    // The proposal with +2/3 prevotes in the round, or an empty hash for nil.
+    fn polka(&self, authority_list: &[Vec<u8>], height: u64, round: u64) -> Option<Vec<u8>> {
+        let proposals = self
+            .votes
+            .iter()
+            .map(|v| &v.msg)
+            .filter(|v| v.height == height && v.round == round && v.vote_type == VoteType::Prevote)
+            .map(|v| &v.proposal)
+            .collect::<HashSet<_>>();
+        proposals
+            .into_iter()
+            .find(|p| self.is_quorum(authority_list, height, round, &VoteType::Prevote, p))
+            .cloned()
+    }
+}
+
+fn check_commits(trace: &Trace, commits: &[Record<Commit>], violations: &mut Vec<Violation>) {
+    let mut first = BTreeMap::new();
+    for commit in commits.iter() {
+        let height = commit.msg.height;
+        let prev = first.entry(height).or_insert(commit);
+        if prev.msg.result != commit.msg.result {
+            violations.push(Violation::ConflictingCommit {
+                height,
+                seqs: vec![prev.seq, commit.seq],
+            });
+        }
+    }
+
+    for commit in commits.iter() {
+        let height = commit.msg.height;
+        let authority_list = match trace.authority_list(height) {
+            Some(list) => list,
+            None => {
+                violations.push(Violation::NoAuthorityList {
+                    height,
+                    seqs: vec![commit.seq],
+                });
+                continue;
+            }
+        };
+        let rounds = trace
+            .votes
+            .iter()
+            .filter(|v| v.msg.height == height)
+            .map(|v| v.msg.round)
+            .collect::<HashSet<_>>();
+        if !rounds.into_iter().any(|round| {
+            trace.is_quorum(
+                authority_list,
+                height,
+                round,
+                &VoteType::Precommit,
+                &commit.msg.result,
+            )
+        }) {
+            violations.push(Violation::UnbackedCommit {
+                height,
+                seqs: vec![commit.seq],
+            });
+        }
+    }
+}
+
+fn check_double_votes(trace: &Trace, violations: &mut Vec<Violation>) {
+    let mut first = HashMap::new();
+    for vote in trace.votes.iter() {
+        let v = &vote.msg;
+        let key = (&v.voter, v.height, v.round, &v.vote_type);
+        let prev = first.entry(key).or_insert(vote);
+        if prev.msg.proposal != v.proposal {
+            violations.push(Violation::DoubleVote {
+                voter: v.voter.clone(),
+                height: v.height,
+                round: v.round,
+                vote_type: v.vote_type.clone(),
+                seqs: vec![prev.seq, vote.seq],
+            });
+        }
+    }
+}
+
+fn check_locks(trace: &Trace, violations: &mut Vec<Violation>) {
+    let mut votes = trace
+        .votes
+        .iter()
+        .filter(|v| v.origin != Origin::ByzantinePeer)
+        .collect::<Vec<_>>();
+    // the precommit of a round locks after the prevote of the round
+    votes.sort_by_key(|v| {
+        let precommit = v.msg.vote_type == VoteType::Precommit;
+        (v.msg.height, v.msg.round, precommit, v.seq)
+    });
+
+    let mut locks: HashMap<(&Vec<u8>, u64), Lock> = HashMap::new();
+    for vote in votes.into_iter() {
+        let v = &vote.msg;
+        if v.proposal.is_empty() {
+            continue;
+        }
+        let key = (&v.voter, v.height);
+        if v.vote_type == VoteType::Precommit {
+            locks.insert(key, (v.round, &v.proposal, vote.seq));
+            continue;
+        }
+
+        let (lock_round, lock_proposal, lock_seq) = match locks.get(&key) {
+            Some(lock) => *lock,
+            None => continue,
+        };
+        if lock_round >= v.round {
+            continue;
+        }
+        let polkas = trace
+            .authority_list(v.height)
+            .map(|list| {
+                (lock_round + 1..v.round)
+                    .map(|round| (round, trace.polka(list, v.height, round)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut lock = Some((lock_round, lock_proposal.as_slice()));
+        let mut polc_round = None;
+        for (round, polka) in polkas.into_iter() {
+            match LockChange::by_polka(polka) {
+                LockChange::Unlock => lock = None,
+                LockChange::Lock(ref p) if p == &v.proposal => polc_round = Some(round),
+                _ => (),
+            }
+        }
+        if !can_prevote(lock, &v.proposal, polc_round, v.round) {
+            violations.push(Violation::LockViolation {
+                voter: v.voter.clone(),
+                height: v.height,
+                round: v.round,
+                seqs: vec![lock_seq, vote.seq],
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::{
+        collection::{
+            storage::{Storage, TempDb},
+            util::Direction,
+        },
+        types::Proposal,
+    };
+
+    fn vote(round: u64, vote_type: VoteType, proposal: u8, voter: u8) -> Msg {
+        Msg::Vote(Vote {
+            height: 1,
+            round,
+            vote_type,
+            proposal: vec![proposal],
+            voter: vec![voter],
+        })
+    }
+
+    fn record(db: &TempDb, msgs: Vec<Msg>) {
+        db.reset();
+        let mut storage = Storage::new(db.path()).unwrap();
+        storage.begin_run(0, None).unwrap();
+        let status = Msg::Status(Status {
+            height: 0,
+            authority_list: (0..4).map(|i| vec![i]).collect(),
+        });
+        storage
+            .insert(status, Direction::Sent, Origin::Framework)
+            .unwrap();
+        for msg in msgs.into_iter() {
+            storage
+                .insert(msg, Direction::Received, Origin::TestingNode)
+                .unwrap();
+        }
+        storage.finish_run(&Ok(())).unwrap();
+    }
+
+    fn commit(result: u8) -> Msg {
+        Msg::Commit(Commit {
+            node: 0,
+            height: 1,
+            result: vec![result],
+        })
+    }
+
+    #[test]
+    fn test_safe_trace() {
+        let db = TempDb::new("safety_safe");
+        let mut msgs = Vec::new();
+        msgs.push(Msg::Proposal(Proposal {
+            height: 1,
+            round: 0,
+            content: vec![1],
+            proposer: vec![1],
+            lock_round: None,
+            lock_votes: Vec::new(),
+        }));
+        // locked on 1 in round 0, and locked on 2 by the prevotes of 2 in round 1
+        msgs.extend((0..4).map(|i| vote(0, VoteType::Prevote, 1, i)));
+        msgs.push(vote(0, VoteType::Precommit, 1, 0));
+        msgs.push(vote(1, VoteType::Prevote, 1, 0));
+        msgs.extend((1..4).map(|i| vote(1, VoteType::Prevote, 2, i)));
+        msgs.extend((0..3).map(|i| vote(1, VoteType::Precommit, 2, i)));
+        msgs.push(vote(2, VoteType::Prevote, 2, 0));
+        msgs.push(commit(2));
+        record(&db, msgs);
+
+        let reports = check_trace(db.path()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].is_safe(), "{:?}", reports[0]);
+    }
+
+    #[test]
+    fn test_nil_polka_unlock() {
+        let db = TempDb::new("safety_unlock");
+        let mut msgs = Vec::new();
+        // locked on 1 in round 0, and unlocked by the nil prevotes in round 1
+        msgs.extend((0..4).map(|i| vote(0, VoteType::Prevote, 1, i)));
+        msgs.push(vote(0, VoteType::Precommit, 1, 0));
+        msgs.extend((0..4).map(|i| {
+            Msg::Vote(Vote {
+                height: 1,
+                round: 1,
+                vote_type: VoteType::Prevote,
+                proposal: Vec::new(),
+                voter: vec![i],
+            })
+        }));
+        msgs.extend((0..4).map(|i| vote(2, VoteType::Prevote, 2, i)));
+        msgs.push(vote(2, VoteType::Precommit, 2, 0));
+        record(&db, msgs.clone());
+        assert!(check_trace(db.path()).unwrap()[0].is_safe());
+
+        // without the nil prevotes, the prevote of 2 breaks the lock
+        msgs.retain(|msg| match msg {
+            Msg::Vote(v) => v.round != 1,
+            _ => true,
+        });
+        record(&db, msgs);
+        let violations = &check_trace(db.path()).unwrap()[0].violations;
+        assert_eq!(violations.len(), 1);
+        match violations[0] {
+            Violation::LockViolation {
+                ref voter,
+                round: 2,
+                ..
+            } => assert_eq!(voter, &vec![0]),
+            ref v => panic!("Get {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_unsafe_trace() {
+        let db = TempDb::new("safety_unsafe");
+        let mut msgs = Vec::new();
+        // seq 2 to 5
+        msgs.extend((0..4).map(|i| vote(0, VoteType::Prevote, 1, i)));
+        // seq 6, 7
+        msgs.push(vote(0, VoteType::Precommit, 1, 0));
+        msgs.push(vote(0, VoteType::Precommit, 2, 0));
+        // seq 8
+        msgs.push(vote(2, VoteType::Prevote, 3, 0));
+        // seq 9, 10
+        msgs.push(commit(1));
+        msgs.push(commit(3));
+        record(&db, msgs);
+
+        let reports = check_trace(db.path()).unwrap();
+        assert_eq!(
+            reports[0].violations,
+            vec![
+                Violation::ConflictingCommit {
+                    height: 1,
+                    seqs: vec![9, 10],
+                },
+                Violation::UnbackedCommit {
+                    height: 1,
+                    seqs: vec![9],
+                },
+                Violation::UnbackedCommit {
+                    height: 1,
+                    seqs: vec![10],
+                },
+                Violation::DoubleVote {
+                    voter: vec![0],
+                    height: 1,
+                    round: 0,
+                    vote_type: VoteType::Precommit,
+                    seqs: vec![6, 7],
+                },
+                Violation::LockViolation {
+                    voter: vec![0],
+                    height: 1,
+                    round: 2,
+                    seqs: vec![7, 8],
+                },
+            ]
+        );
+        assert_eq!(reports[0].violations[4].seqs(), &[7, 8]);
+    }
+}
@@ -1,109 +1,371 @@
-use crate::whitebox::collection::util::*;
+use crate::error::FrameError;
+use crate::whitebox::{
+    collection::util::*,
+    types::{BftResult, FrameResult},
+};
 use rusqlite::{params, Connection, Result, NO_PARAMS};
 
+use std::time::Duration;
+
+// The version of the schema, which is kept in the `user_version` of the database. A
+// database of another version is refused rather than mixed with the new rows.
+pub(crate) const SCHEMA_VERSION: i64 = 4;
+
+// The tables of a baseline database, which has no schema version.
+const BASELINE_TABLES: [&str; 6] = ["proposal", "vote", "cmt", "feed", "status", "latency"];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        run_id      INTEGER PRIMARY KEY AUTOINCREMENT,
+        seed        INTEGER NOT NULL,
+        suite       TEXT,
+        start_time  TEXT NOT NULL,
+        end_time    TEXT,
+        outcome     TEXT,
+        error       TEXT
+    );
+    CREATE TABLE IF NOT EXISTS proposal (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        direction   TEXT NOT NULL,
+        origin      TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        round       INTEGER NOT NULL,
+        proposer    BLOB NOT NULL,
+        proposal    TEXT NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+    CREATE INDEX IF NOT EXISTS proposal_proposer ON proposal (run_id, proposer);
+    CREATE TABLE IF NOT EXISTS vote (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        direction   TEXT NOT NULL,
+        origin      TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        round       INTEGER NOT NULL,
+        vote_type   TEXT NOT NULL,
+        voter       BLOB NOT NULL,
+        vote        TEXT NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+    CREATE INDEX IF NOT EXISTS vote_round ON vote (run_id, height, round, vote_type);
+    CREATE TABLE IF NOT EXISTS cmt (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        direction   TEXT NOT NULL,
+        origin      TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        cmt         TEXT NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+    CREATE TABLE IF NOT EXISTS feed (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        direction   TEXT NOT NULL,
+        origin      TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        feed        TEXT NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+    CREATE TABLE IF NOT EXISTS status (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        direction   TEXT NOT NULL,
+        origin      TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        status      TEXT NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+    CREATE TABLE IF NOT EXISTS latency (
+        run_id      INTEGER NOT NULL REFERENCES runs(run_id),
+        seq         INTEGER NOT NULL,
+        timestamp   TEXT NOT NULL,
+        step        INTEGER,
+        height      INTEGER NOT NULL,
+        round       INTEGER NOT NULL,
+        latency_us  INTEGER NOT NULL,
+        PRIMARY KEY (run_id, seq)
+    );
+";
+
+/// The storage of the messages of the tests. Each test is recorded as a run in the `runs`
+/// table, and the messages are linked to the run by the `run_id`. The rows of a run are
+/// keyed by a sequence number shared by all the tables, which keeps the order of the
+/// messages even if they are stored at the same time. A message row also records the
+/// direction, the origin and the index of the test step of the message. The step is
+/// `NULL` for the messages of the initialization of a run.
 #[derive(Debug)]
-pub(crate) struct Storage(Connection);
+pub(crate) struct Storage {
+    conn: Connection,
+    run_id: Option<i64>,
+    seq: i64,
+    step: Option<i64>,
+}
 
 impl Storage {
-    pub(crate) fn new(db_path: &str) -> Self {
-        let conn = Connection::open(db_path).expect("Create SQLite failed!");
-        conn.execute(
-            "CREATE TABLE proposal (
-                timestamp   TEXT PRIMARY KEY,
-                height      INTEGER NOT NULL,
-                round       INTEGER NOT NULL,
-                proposal    TEXT NOT NULL
-            )",
-            NO_PARAMS,
-        )
-        .expect("Create proposal table failed!");
-        conn.execute(
-            "CREATE TABLE vote (
-                timestamp   TEXT PRIMARY KEY,
-                height      INTEGER NOT NULL,
-                round       INTEGER NOT NULL,
-                voter       BLOB NOT NULL,
-                vote        TEXT NOT NULL
-            )",
-            NO_PARAMS,
-        )
-        .expect("Create vote table failed!");
-        conn.execute(
-            "CREATE TABLE cmt (
-                timestamp   TEXT PRIMARY KEY,
-                height      INTEGER NOT NULL,
-                cmt         TEXT NOT NULL
-            )",
-            NO_PARAMS,
-        )
-        .expect("Create commit table failed!");
-        conn.execute(
-            "CREATE TABLE feed (
-                timestamp   TEXT PRIMARY KEY,
-                height      INTEGER NOT NULL,
-                feed        TEXT NOT NULL
-            )",
-            NO_PARAMS,
-        )
-        .expect("Create vote table failed!");
-        conn.execute(
-            "CREATE TABLE status (
-                timestamp   TEXT PRIMARY KEY,
-                height      INTEGER NOT NULL,
-                status      TEXT NOT NULL
-            )",
-            NO_PARAMS,
-        )
-        .expect("Create vote table failed!");
-
-        Storage(conn)
-    }
-
-    pub(crate) fn insert(&self, msg: Msg) -> Result<()> {
+    /// Open the database of the `db_path`, which is created if it does not exist. The
+    /// tables of a baseline database, which has no schema version, are kept as the
+    /// `legacy_*` tables, since their rows belong to no run. A database of another schema
+    /// version is refused.
+    pub(crate) fn new(db_path: &str) -> FrameResult<Self> {
+        let conn = Connection::open(db_path).map_err(storage_err)?;
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+            .map_err(storage_err)?;
+        if version == 0 {
+            migrate_baseline(&conn, db_path)?;
+        } else if version != SCHEMA_VERSION {
+            return Err(FrameError::Storage(format!(
+                "{:?} has schema version {}, but {} is required",
+                db_path, version, SCHEMA_VERSION
+            )));
+        }
+        conn.execute_batch(SCHEMA).map_err(storage_err)?;
+        conn.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+            .map_err(storage_err)?;
+
+        Ok(Storage {
+            conn,
+            run_id: None,
+            seq: 0,
+            step: None,
+        })
+    }
+
+    /// Start a new run, the following messages are linked to it until the next run starts.
+    pub(crate) fn begin_run(&mut self, seed: u64, suite: Option<&str>) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (seed, suite, start_time) VALUES (?1, ?2, ?3)",
+            params![seed as i64, suite, time::get_time()],
+        )?;
+        let run_id = self.conn.last_insert_rowid();
+        self.run_id = Some(run_id);
+        self.seq = 0;
+        self.step = None;
+        Ok(run_id)
+    }
+
+    /// Set the index of the test step of the following messages.
+    pub(crate) fn set_step(&mut self, step: usize) {
+        self.step = Some(step as i64);
+    }
+
+    /// Record the end time and the outcome of the current run.
+    pub(crate) fn finish_run(&mut self, res: &BftResult<()>) -> Result<()> {
+        let (outcome, error) = match res {
+            Ok(()) => ("passed", None),
+            Err(e) => ("failed", Some(format!("{:?}", e))),
+        };
+        self.conn.execute(
+            "UPDATE runs SET end_time = ?1, outcome = ?2, error = ?3 WHERE run_id = ?4",
+            params![time::get_time(), outcome, error, self.run_id],
+        )?;
+        Ok(())
+    }
+
+    /// Store a message of the current run and return its sequence number.
+    pub(crate) fn insert(&mut self, msg: Msg, direction: Direction, origin: Origin) -> Result<i64> {
+        let seq = self.next_seq();
+        let (direction, origin) = (direction.as_str(), origin.as_str());
         match msg {
             Msg::Proposal(p) => {
                 let p = StorageProposal::from_proposal(p);
-                self.0.execute(
-                    "INSERT INTO proposal (timestamp, height, round, proposal)
-                        VALUES (?1, ?2, ?3, ?4)",
-                    params![p.timestamp, p.height, p.round, p.proposal],
+                self.conn.execute(
+                    "INSERT INTO proposal (run_id, seq, timestamp, direction, origin, step,
+                        height, round, proposer, proposal)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        self.run_id,
+                        seq,
+                        p.timestamp,
+                        direction,
+                        origin,
+                        self.step,
+                        p.height,
+                        p.round,
+                        p.proposer,
+                        p.proposal
+                    ],
                 )?;
             }
             Msg::Vote(v) => {
                 let v = StorageVote::from_vote(v);
-                self.0.execute(
-                    "INSERT INTO vote (timestamp, height, round, voter, vote)
-                        VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![v.timestamp, v.height, v.round, v.voter, v.vote],
+                self.conn.execute(
+                    "INSERT INTO vote (run_id, seq, timestamp, direction, origin, step,
+                        height, round, vote_type, voter, vote)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        self.run_id,
+                        seq,
+                        v.timestamp,
+                        direction,
+                        origin,
+                        self.step,
+                        v.height,
+                        v.round,
+                        v.vote_type,
+                        v.voter,
+                        v.vote
+                    ],
                 )?;
             }
             Msg::Commit(c) => {
                 let c = StorageCommit::from_commit(c);
-                self.0.execute(
-                    "INSERT INTO cmt (timestamp, height, cmt)
-                        VALUES (?1, ?2, ?3)",
-                    params![c.timestamp, c.height, c.commit],
+                self.conn.execute(
+                    "INSERT INTO cmt (run_id, seq, timestamp, direction, origin, step,
+                        height, cmt)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.run_id,
+                        seq,
+                        c.timestamp,
+                        direction,
+                        origin,
+                        self.step,
+                        c.height,
+                        c.commit
+                    ],
                 )?;
             }
             Msg::Feed(f) => {
                 let f = StorageFeed::from_feed(f);
-                self.0.execute(
-                    "INSERT INTO feed (timestamp, height, feed)
-                        VALUES (?1, ?2, ?3)",
-                    params![f.timestamp, f.height, f.proposal],
+                self.conn.execute(
+                    "INSERT INTO feed (run_id, seq, timestamp, direction, origin, step,
+                        height, feed)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.run_id,
+                        seq,
+                        f.timestamp,
+                        direction,
+                        origin,
+                        self.step,
+                        f.height,
+                        f.proposal
+                    ],
                 )?;
             }
             Msg::Status(s) => {
                 let s = StorageStatus::from_status(s);
-                self.0.execute(
-                    "INSERT INTO status (timestamp, height, status)
-                        VALUES (?1, ?2, ?3)",
-                    params![s.timestamp, s.height, s.authority],
+                self.conn.execute(
+                    "INSERT INTO status (run_id, seq, timestamp, direction, origin, step,
+                        height, status)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.run_id,
+                        seq,
+                        s.timestamp,
+                        direction,
+                        origin,
+                        self.step,
+                        s.height,
+                        s.authority
+                    ],
                 )?;
             }
         }
+        Ok(seq)
+    }
+
+    pub(crate) fn insert_latency(
+        &mut self,
+        height: u64,
+        round: u64,
+        latency: Duration,
+    ) -> Result<()> {
+        let seq = self.next_seq();
+        self.conn.execute(
+            "INSERT INTO latency (run_id, seq, timestamp, step, height, round, latency_us)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.run_id,
+                seq,
+                time::get_time(),
+                self.step,
+                height as i64,
+                round as i64,
+                latency.as_micros() as i64
+            ],
+        )?;
         Ok(())
     }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+}
+
+// Rename the tables of a baseline database to the `legacy_*` ones.
+fn migrate_baseline(conn: &Connection, db_path: &str) -> FrameResult<()> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .map_err(storage_err)?;
+    let tables = stmt
+        .query_map(NO_PARAMS, |row| row.get::<_, String>(0))
+        .map_err(storage_err)?
+        .collect::<Result<Vec<_>>>()
+        .map_err(storage_err)?;
+    if let Some(table) = tables
+        .iter()
+        .find(|t| !BASELINE_TABLES.contains(&t.as_str()))
+    {
+        return Err(FrameError::Storage(format!(
+            "{:?} has an unknown table {:?}",
+            db_path, table
+        )));
+    }
+    let sql = tables
+        .iter()
+        .map(|t| format!("ALTER TABLE {} RENAME TO legacy_{};", t, t))
+        .collect::<String>();
+    conn.execute_batch(&format!("BEGIN; {} COMMIT;", sql))
+        .map_err(storage_err)
+}
+
+pub(crate) fn storage_err<E: ToString>(e: E) -> FrameError {
+    FrameError::Storage(e.to_string())
+}
+
+/// A database of a test under the temporary directory, which is removed when dropped.
+#[cfg(test)]
+pub(crate) struct TempDb(String);
+
+#[cfg(test)]
+impl TempDb {
+    pub(crate) fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("bft_test_{}_{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        TempDb(path)
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.0
+    }
+
+    /// Remove the database to start over.
+    pub(crate) fn reset(&self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.reset();
+    }
 }
 
 #[cfg(test)]
@@ -177,13 +439,86 @@ mod test {
 
     #[test]
     fn test_db() {
-        let conn = Storage::new("db/test.db");
-        let message = generate_msg();
-        for msg in message.into_iter() {
-            let res = conn.insert(msg.clone());
-            if res.is_err() {
-                panic!("SQLite error {:?}", res);
+        let db = TempDb::new("storage");
+        let db_path = db.path();
+        for seed in 0..2 {
+            let mut conn = Storage::new(db_path).unwrap();
+            let run_id = conn.begin_run(seed, Some("test")).unwrap();
+            assert_eq!(run_id, seed as i64 + 1);
+            conn.set_step(0);
+            let message = generate_msg();
+            for msg in message.into_iter() {
+                let res = conn.insert(msg.clone(), Direction::Sent, Origin::HonestPeer);
+                if res.is_err() {
+                    panic!("SQLite error {:?}", res);
+                }
             }
+            conn.insert_latency(1, 0, Duration::from_millis(3)).unwrap();
+            conn.finish_run(&Ok(())).unwrap();
+        }
+
+        let conn = Connection::open(db_path).unwrap();
+        let count =
+            |sql: &str| -> i64 { conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap() };
+        assert_eq!(
+            count("SELECT COUNT(*) FROM runs WHERE outcome = 'passed'"),
+            2
+        );
+        assert_eq!(count("SELECT COUNT(*) FROM vote WHERE run_id = 2"), 2);
+        // the sequence numbers of a run are shared by all the tables
+        assert_eq!(count("SELECT MAX(seq) FROM latency WHERE run_id = 2"), 8);
+        assert_eq!(count("SELECT seq FROM cmt WHERE run_id = 1"), 6);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM proposal WHERE origin = 'honest_peer' AND step = 0"),
+            4
+        );
+    }
+
+    #[test]
+    fn test_db_version() {
+        // the tables of a baseline database are kept as the legacy ones
+        let db = TempDb::new("storage_version");
+        let db_path = db.path();
+        Connection::open(db_path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE cmt (timestamp TEXT PRIMARY KEY, height INTEGER, cmt TEXT);
+                    INSERT INTO cmt VALUES ('0', 1, 'commit');",
+            )
+            .unwrap();
+        let mut storage = Storage::new(db_path).unwrap();
+        storage.begin_run(0, None).unwrap();
+        let commit = Msg::Commit(Commit {
+            node: 0,
+            height: 1,
+            result: vec![1],
+        });
+        storage
+            .insert(commit, Direction::Received, Origin::TestingNode)
+            .unwrap();
+        let conn = Connection::open(db_path).unwrap();
+        let count =
+            |sql: &str| -> i64 { conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM legacy_cmt"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM cmt"), 1);
+        assert!(Storage::new(db_path).is_ok());
+
+        // a database of another schema is refused
+        conn.execute_batch("CREATE TABLE seed (timestamp TEXT PRIMARY KEY)")
+            .unwrap();
+        conn.execute_batch("PRAGMA user_version = 99").unwrap();
+        match Storage::new(db_path) {
+            Err(FrameError::Storage(_)) => (),
+            res => panic!("Get {:?}", res),
+        }
+        db.reset();
+        Connection::open(db_path)
+            .unwrap()
+            .execute_batch("CREATE TABLE seed (timestamp TEXT PRIMARY KEY)")
+            .unwrap();
+        match Storage::new(db_path) {
+            Err(FrameError::Storage(_)) => (),
+            res => panic!("Get {:?}", res),
         }
     }
 }
@@ -0,0 +1,378 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    collection::{
+        storage::{storage_err, SCHEMA_VERSION},
+        util::{vote_type_str, Direction, Msg, Origin},
+    },
+    types::{Commit, Feed, FrameResult, Proposal, Status, Vote, VoteType},
+};
+use rusqlite::{types::ToSql, Connection, OpenFlags, NO_PARAMS};
+use serde::de::DeserializeOwned;
+use time::Timespec;
+
+/// A message recorded in a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record<T> {
+    /// The ID of the run of the message.
+    pub run_id: u64,
+    /// The sequence number of the message in the run.
+    pub seq: u64,
+    /// The time when the message is stored.
+    pub timestamp: Timespec,
+    /// Whether the message is sent to or received from the testing node.
+    pub direction: Direction,
+    /// The origin of the message.
+    pub origin: Origin,
+    /// The index of the test step of the message. It is `None` for the messages of the
+    /// initialization of a run.
+    pub step: Option<usize>,
+    /// The message.
+    pub msg: T,
+}
+
+impl<T> Record<T> {
+    pub(crate) fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Record<U> {
+        Record {
+            run_id: self.run_id,
+            seq: self.seq,
+            timestamp: self.timestamp,
+            direction: self.direction,
+            origin: self.origin,
+            step: self.step,
+            msg: f(self.msg),
+        }
+    }
+}
+
+/// The outcome of a finished run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The test passed.
+    Passed,
+    /// The test failed with the error.
+    Failed(String),
+}
+
+/// A run recorded in a trace, which is a test done by the actuator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    /// The ID of the run.
+    pub run_id: u64,
+    /// The seed of the actuator.
+    pub seed: u64,
+    /// The name of the test suite or the scenario, if the test has one.
+    pub suite: Option<String>,
+    /// The time when the run starts.
+    pub start_time: Timespec,
+    /// The time when the run ends. It is `None` if the run has not finished.
+    pub end_time: Option<Timespec>,
+    /// The outcome of the run. It is `None` if the run has not finished.
+    pub outcome: Option<RunOutcome>,
+}
+
+/// A reader of the trace recorded by the actuator. The messages are queried by the run,
+/// and returned in order of the sequence numbers.
+#[derive(Debug)]
+pub struct TraceReader {
+    conn: Connection,
+}
+
+type Row = (i64, i64, Timespec, String, String, Option<i64>, String);
+
+impl TraceReader {
+    /// A function to open a database recorded by the actuator as read-only. Return an
+    /// error if the database does not exist or is of another schema version.
+    pub fn open(path: &str) -> FrameResult<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(storage_err)?;
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+            .map_err(storage_err)?;
+        if version != SCHEMA_VERSION {
+            return Err(FrameError::Storage(format!(
+                "{:?} has schema version {}, but {} is required",
+                path, version, SCHEMA_VERSION
+            )));
+        }
+        Ok(TraceReader { conn })
+    }
+
+    /// A function to get all the runs in order.
+    pub fn runs(&self) -> FrameResult<Vec<Run>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT run_id, seed, suite, start_time, end_time, outcome, error
+                    FROM runs ORDER BY run_id",
+            )
+            .map_err(storage_err)?;
+        let runs = stmt
+            .query_map(NO_PARAMS, |row| {
+                let outcome = match row.get::<_, Option<String>>(5)? {
+                    Some(ref outcome) if outcome == "passed" => Some(RunOutcome::Passed),
+                    Some(_) => Some(RunOutcome::Failed(
+                        row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+                    )),
+                    None => None,
+                };
+                Ok(Run {
+                    run_id: row.get::<_, i64>(0)? as u64,
+                    seed: row.get::<_, i64>(1)? as u64,
+                    suite: row.get(2)?,
+                    start_time: row.get(3)?,
+                    end_time: row.get(4)?,
+                    outcome,
+                })
+            })
+            .map_err(storage_err)?;
+        runs.collect::<rusqlite::Result<Vec<Run>>>()
+            .map_err(storage_err)
+    }
+
+    /// A function to get the votes of the `vote_type` at the `height` and the `round` of
+    /// a run, including the sent and the received ones.
+    pub fn votes_at(
+        &self,
+        run_id: u64,
+        height: u64,
+        round: u64,
+        vote_type: VoteType,
+    ) -> FrameResult<Vec<Record<Vote>>> {
+        self.query(
+            "vote",
+            "run_id = ?1 AND height = ?2 AND round = ?3 AND vote_type = ?4",
+            &[
+                &(run_id as i64),
+                &(height as i64),
+                &(round as i64),
+                &vote_type_str(&vote_type),
+            ],
+        )
+    }
+
+    /// A function to get the commits of a run.
+    pub fn commits_for_run(&self, run_id: u64) -> FrameResult<Vec<Record<Commit>>> {
+        self.query("cmt", "run_id = ?1", &[&(run_id as i64)])
+    }
+
+    /// A function to get the proposals of a run proposed by the `proposer`.
+    pub fn proposals_by_proposer(
+        &self,
+        run_id: u64,
+        proposer: &[u8],
+    ) -> FrameResult<Vec<Record<Proposal>>> {
+        self.query(
+            "proposal",
+            "run_id = ?1 AND proposer = ?2",
+            &[&(run_id as i64), &proposer],
+        )
+    }
+
+    /// A function to get the messages of a run whose sequence numbers are between the
+    /// `seq_a` and the `seq_b`, both inclusive.
+    pub fn messages_between(
+        &self,
+        run_id: u64,
+        seq_a: u64,
+        seq_b: u64,
+    ) -> FrameResult<Vec<Record<Msg>>> {
+        let (low, high) = if seq_a <= seq_b {
+            (seq_a, seq_b)
+        } else {
+            (seq_b, seq_a)
+        };
+        let filter = "run_id = ?1 AND seq BETWEEN ?2 AND ?3";
+        let to_sql = |n: u64| n.min(i64::MAX as u64) as i64;
+        let params: &[&dyn ToSql] = &[&(run_id as i64), &to_sql(low), &to_sql(high)];
+
+        let mut msgs = Vec::new();
+        for r in self.query::<Proposal>("proposal", filter, params)? {
+            msgs.push(r.map(Msg::Proposal));
+        }
+        for r in self.query::<Vote>("vote", filter, params)? {
+            msgs.push(r.map(Msg::Vote));
+        }
+        for r in self.query::<Commit>("cmt", filter, params)? {
+            msgs.push(r.map(Msg::Commit));
+        }
+        for r in self.query::<Feed>("feed", filter, params)? {
+            msgs.push(r.map(Msg::Feed));
+        }
+        for r in self.query::<Status>("status", filter, params)? {
+            msgs.push(r.map(Msg::Status));
+        }
+        msgs.sort_by_key(|r| r.seq);
+        Ok(msgs)
+    }
+
+    /// A function to get all the messages of a run.
+    pub fn messages(&self, run_id: u64) -> FrameResult<Vec<Record<Msg>>> {
+        self.messages_between(run_id, 0, u64::MAX)
+    }
+
+    // Query the rows of a message table, whose message column is of the same name as the
+    // table.
+    fn query<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        filter: &str,
+        params: &[&dyn ToSql],
+    ) -> FrameResult<Vec<Record<T>>> {
+        let sql = format!(
+            "SELECT run_id, seq, timestamp, direction, origin, step, {}
+                FROM {} WHERE {} ORDER BY seq",
+            table, table, filter
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(storage_err)?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ))
+            })
+            .map_err(storage_err)?
+            .collect::<rusqlite::Result<Vec<Row>>>()
+            .map_err(storage_err)?;
+
+        rows.into_iter()
+            .map(|(run_id, seq, timestamp, direction, origin, step, msg)| {
+                Ok(Record {
+                    run_id: run_id as u64,
+                    seq: seq as u64,
+                    timestamp,
+                    direction: Direction::parse(&direction).ok_or_else(|| {
+                        FrameError::Storage(format!("illegal direction {:?}", direction))
+                    })?,
+                    origin: Origin::parse(&origin).ok_or_else(|| {
+                        FrameError::Storage(format!("illegal origin {:?}", origin))
+                    })?,
+                    step: step.map(|s| s as usize),
+                    msg: serde_json::from_str(&msg).map_err(storage_err)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::collection::storage::{Storage, TempDb};
+
+    fn vote(round: u64, vote_type: VoteType, voter: u8) -> Vote {
+        Vote {
+            height: 1,
+            round,
+            vote_type,
+            proposal: vec![1],
+            voter: vec![voter],
+        }
+    }
+
+    #[test]
+    fn test_trace_reader() {
+        let db = TempDb::new("trace");
+        let db_path = db.path();
+        let mut storage = Storage::new(db_path).unwrap();
+        storage.begin_run(7, Some("trace")).unwrap();
+        storage.set_step(0);
+        let proposal = Proposal {
+            height: 1,
+            round: 0,
+            content: vec![1],
+            proposer: vec![1],
+            lock_round: None,
+            lock_votes: Vec::new(),
+        };
+        let msgs = vec![
+            Msg::Proposal(proposal.clone()),
+            Msg::Vote(vote(0, VoteType::Prevote, 1)),
+            Msg::Vote(vote(0, VoteType::Precommit, 1)),
+            Msg::Vote(vote(1, VoteType::Prevote, 1)),
+        ];
+        for msg in msgs.into_iter() {
+            storage
+                .insert(msg, Direction::Sent, Origin::HonestPeer)
+                .unwrap();
+        }
+        storage.set_step(1);
+        let commit = Commit {
+            node: 0,
+            height: 1,
+            result: vec![1],
+        };
+        storage
+            .insert(
+                Msg::Commit(commit.clone()),
+                Direction::Received,
+                Origin::TestingNode,
+            )
+            .unwrap();
+        storage.finish_run(&Ok(())).unwrap();
+        storage.begin_run(8, None).unwrap();
+
+        let reader = TraceReader::open(db_path).unwrap();
+        let runs = reader.runs().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].seed, 7);
+        assert_eq!(runs[0].suite, Some("trace".to_string()));
+        assert_eq!(runs[0].outcome, Some(RunOutcome::Passed));
+        assert_eq!(runs[1].outcome, None);
+
+        let votes = reader.votes_at(1, 1, 0, VoteType::Precommit).unwrap();
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].seq, 3);
+        assert_eq!(votes[0].msg, vote(0, VoteType::Precommit, 1));
+
+        let commits = reader.commits_for_run(1).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].msg, commit);
+        assert_eq!(commits[0].direction, Direction::Received);
+        assert_eq!(commits[0].origin, Origin::TestingNode);
+        assert_eq!(commits[0].step, Some(1));
+
+        assert_eq!(
+            reader.proposals_by_proposer(1, &[1]).unwrap()[0].msg,
+            proposal
+        );
+        assert!(reader.proposals_by_proposer(1, &[2]).unwrap().is_empty());
+
+        // the votes and the proposals are filtered by the indexes
+        let plan = |sql: &str| -> String {
+            reader
+                .conn
+                .query_row(&format!("EXPLAIN QUERY PLAN {}", sql), NO_PARAMS, |row| {
+                    row.get(3)
+                })
+                .unwrap()
+        };
+        assert!(plan(
+            "SELECT * FROM vote WHERE run_id = 1 AND height = 1 AND round = 0
+                AND vote_type = 'prevote'"
+        )
+        .contains("vote_round"));
+        assert!(
+            plan("SELECT * FROM proposal WHERE run_id = 1 AND proposer = x'01'")
+                .contains("proposal_proposer")
+        );
+
+        let seqs = reader
+            .messages_between(1, 4, 2)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.seq)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![2, 3, 4]);
+        assert_eq!(reader.messages(1).unwrap().len(), 5);
+        assert!(reader.messages(2).unwrap().is_empty());
+
+        let missing = TempDb::new("trace_missing");
+        assert!(TraceReader::open(missing.path()).is_err());
+    }
+}
@@ -1,13 +1,87 @@
-use crate::whitebox::types::{Commit, Feed, Proposal, Status, Vote};
+use crate::whitebox::types::{Commit, Feed, Proposal, Status, Vote, VoteType};
 use serde_json::to_string;
 use time::Timespec;
 
+/// The direction of a stored message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The message is sent to the testing node.
+    Sent,
+    /// The message is received from the testing node.
+    Received,
+}
+
+impl Direction {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sent" => Some(Direction::Sent),
+            "received" => Some(Direction::Received),
+            _ => None,
+        }
+    }
+}
+
+/// The origin of a stored message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// The framework itself, which sends the feeds and the status.
+    Framework,
+    /// A simulated node which behaves correctly.
+    HonestPeer,
+    /// A simulated node which votes for a byzantine proposal.
+    ByzantinePeer,
+    /// The testing node.
+    TestingNode,
+}
+
+impl Origin {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Origin::Framework => "framework",
+            Origin::HonestPeer => "honest_peer",
+            Origin::ByzantinePeer => "byzantine_peer",
+            Origin::TestingNode => "testing_node",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "framework" => Some(Origin::Framework),
+            "honest_peer" => Some(Origin::HonestPeer),
+            "byzantine_peer" => Some(Origin::ByzantinePeer),
+            "testing_node" => Some(Origin::TestingNode),
+            _ => None,
+        }
+    }
+}
+
+// The value of the `vote_type` column of a stored vote.
+pub(crate) fn vote_type_str(vote_type: &VoteType) -> &'static str {
+    match vote_type {
+        VoteType::Prevote => "prevote",
+        VoteType::Precommit => "precommit",
+    }
+}
+
+/// A stored message.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) enum Msg {
+pub enum Msg {
+    /// Proposal message.
     Proposal(Proposal),
+    /// Vote message.
     Vote(Vote),
+    /// Commit message.
     Commit(Commit),
+    /// Proposal content message.
     Feed(Feed),
+    /// Rich status message.
     Status(Status),
 }
 
@@ -16,6 +90,7 @@ pub(crate) struct StorageProposal {
     pub(crate) timestamp: Timespec,
     pub(crate) height: i64,
     pub(crate) round: i64,
+    pub(crate) proposer: Vec<u8>,
     pub(crate) proposal: String,
 }
 
@@ -25,6 +100,7 @@ impl StorageProposal {
             timestamp: time::get_time(),
             height: proposal.height as i64,
             round: proposal.round as i64,
+            proposer: proposal.proposer.clone(),
             proposal: to_string(&proposal).unwrap(),
         }
     }
@@ -35,6 +111,7 @@ pub(crate) struct StorageVote {
     pub(crate) timestamp: Timespec,
     pub(crate) height: i64,
     pub(crate) round: i64,
+    pub(crate) vote_type: &'static str,
     pub(crate) voter: Vec<u8>,
     pub(crate) vote: String,
 }
@@ -45,6 +122,7 @@ impl StorageVote {
             timestamp: time::get_time(),
             height: vote.height as i64,
             round: vote.round as i64,
+            vote_type: vote_type_str(&vote.vote_type),
             voter: vote.voter.clone(),
             vote: to_string(&vote).unwrap(),
         }
@@ -0,0 +1,248 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    correctness::scenario::{Scenario, Step},
+    types::{Address, FrameResult},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scenario file. It can be written in TOML, JSON or YAML, and the format is
+/// determined by the file extension. A TOML scenario file looks like:
+///
+/// ```toml
+/// name = "one offline node"
+/// authority_list = [[0], [1], [2], [3]]
+/// height = 0
+///
+/// [[steps]]
+/// step = "round"
+/// prevote = ["normal", "offline", "normal"]
+/// precommit = ["normal", "normal", "offline"]
+///
+/// [[steps]]
+/// step = "expect_commit"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioFile {
+    /// The name of the scenario.
+    pub name: String,
+    /// The description of the scenario.
+    #[serde(default)]
+    pub description: String,
+    /// The authority list. The first one should be the address of the testing node.
+    pub authority_list: Vec<Address>,
+    /// The initial height. If it is `None`, the actuator goes on with its current height.
+    #[serde(default)]
+    pub height: Option<u64>,
+    /// The initial round. If it is `None`, the actuator goes on with its current round.
+    #[serde(default)]
+    pub round: Option<u64>,
+    /// The steps of the scenario.
+    pub steps: Vec<Step>,
+}
+
+impl ScenarioFile {
+    /// A function to validate the scenario file and build the scenario.
+    pub fn scenario(&self) -> FrameResult<Scenario> {
+        let mut addresses = HashSet::new();
+        for address in self.authority_list.iter() {
+            if !addresses.insert(address) {
+                return Err(FrameError::IllegalScenario(format!(
+                    "duplicate address {:?} in authority list of {:?}",
+                    address, self.name
+                )));
+            }
+        }
+
+        let mut builder = Scenario::builder(self.authority_list.len());
+        for step in self.steps.iter() {
+            builder.step(step.clone());
+        }
+        builder.build().map_err(|e| match e {
+            FrameError::IllegalScenario(reason) => {
+                FrameError::IllegalScenario(format!("{} in {:?}", reason, self.name))
+            }
+            e => e,
+        })
+    }
+}
+
+/// A function to parse a scenario file with the given format, which is one of
+/// `toml`, `json`, `yaml` or `yml`. The scenario file is validated after parsing.
+pub fn parse_scenario(content: &str, format: &str) -> FrameResult<ScenarioFile> {
+    let file: ScenarioFile = match format {
+        "toml" => toml::from_str(content).map_err(|e| FrameError::LoadScenario(e.to_string()))?,
+        "json" => {
+            serde_json::from_str(content).map_err(|e| FrameError::LoadScenario(e.to_string()))?
+        }
+        "yaml" | "yml" => {
+            serde_yaml::from_str(content).map_err(|e| FrameError::LoadScenario(e.to_string()))?
+        }
+        _ => {
+            return Err(FrameError::LoadScenario(format!(
+                "unknown scenario format {:?}",
+                format
+            )))
+        }
+    };
+    file.scenario()?;
+    Ok(file)
+}
+
+/// A function to load a scenario file. The format is determined by the file extension.
+pub fn load_scenario<P: AsRef<Path>>(path: P) -> FrameResult<ScenarioFile> {
+    let path = path.as_ref();
+    let format = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let content = fs::read_to_string(path)
+        .map_err(|e| FrameError::LoadScenario(format!("{:?}: {}", path, e)))?;
+    parse_scenario(&content, &format).map_err(|e| match e {
+        FrameError::LoadScenario(reason) => {
+            FrameError::LoadScenario(format!("{:?}: {}", path, reason))
+        }
+        FrameError::IllegalScenario(reason) => {
+            FrameError::IllegalScenario(format!("{:?}: {}", path, reason))
+        }
+        e => e,
+    })
+}
+
+/// A function to load all the scenario files in a directory in the order of file
+/// name. Files with other extensions are ignored.
+pub fn load_scenario_dir<P: AsRef<Path>>(dir: P) -> FrameResult<Vec<(PathBuf, ScenarioFile)>> {
+    let dir = dir.as_ref();
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| FrameError::LoadScenario(format!("{:?}: {}", dir, e)))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| {
+                        ["toml", "json", "yaml", "yml"].contains(&ext.to_lowercase().as_str())
+                    })
+                    .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut res = Vec::new();
+    for path in paths.into_iter() {
+        let file = load_scenario(&path)?;
+        res.push((path, file));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::correctness::scenario::{Behavior, RoundPlan};
+
+    fn expect_file() -> ScenarioFile {
+        ScenarioFile {
+            name: "one offline".to_string(),
+            description: String::new(),
+            authority_list: vec![vec![0], vec![1], vec![2], vec![3]],
+            height: Some(0),
+            round: None,
+            steps: vec![
+                Step::Round(RoundPlan::new(
+                    vec![Behavior::Normal, Behavior::Offline, Behavior::Normal],
+                    vec![Behavior::Normal, Behavior::Normal, Behavior::Nil],
+                )),
+                Step::ExpectCommit,
+                Step::SkipRound,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let toml = r#"
+            name = "one offline"
+            authority_list = [[0], [1], [2], [3]]
+            height = 0
+
+            [[steps]]
+            step = "round"
+            prevote = ["normal", "offline", "normal"]
+            precommit = ["normal", "normal", "nil"]
+
+            [[steps]]
+            step = "expect_commit"
+
+            [[steps]]
+            step = "skip_round"
+        "#;
+        assert_eq!(parse_scenario(toml, "toml").unwrap(), expect_file());
+
+        let json = r#"{
+            "name": "one offline",
+            "authority_list": [[0], [1], [2], [3]],
+            "height": 0,
+            "steps": [
+                {
+                    "step": "round",
+                    "prevote": ["normal", "offline", "normal"],
+                    "precommit": ["normal", "normal", "nil"]
+                },
+                { "step": "expect_commit" },
+                { "step": "skip_round" }
+            ]
+        }"#;
+        assert_eq!(parse_scenario(json, "json").unwrap(), expect_file());
+
+        let yaml = "
+name: one offline
+authority_list: [[0], [1], [2], [3]]
+height: 0
+steps:
+  - step: round
+    prevote: [normal, offline, normal]
+    precommit: [normal, normal, nil]
+  - step: expect_commit
+  - step: skip_round
+";
+        assert_eq!(parse_scenario(yaml, "yaml").unwrap(), expect_file());
+    }
+
+    #[test]
+    fn test_illegal_file() {
+        // unknown behavior
+        let json = r#"{
+            "name": "bad",
+            "authority_list": [[0], [1], [2], [3]],
+            "steps": [{ "step": "round", "prevote": ["good"], "precommit": [] }]
+        }"#;
+        assert!(parse_scenario(json, "json").is_err());
+
+        // plan length mismatch
+        let mut file = expect_file();
+        file.authority_list.push(vec![4]);
+        assert!(file.scenario().is_err());
+
+        // duplicate address
+        let mut file = expect_file();
+        file.authority_list[3] = vec![0];
+        assert!(file.scenario().is_err());
+
+        assert!(parse_scenario("", "xml").is_err());
+    }
+
+    #[test]
+    fn test_load_example_dir() {
+        let files = load_scenario_dir("examples/scenarios").unwrap();
+        assert!(!files.is_empty());
+        for (_, file) in files.iter() {
+            assert!(file.scenario().is_ok());
+        }
+    }
+}
@@ -1,4 +1,14 @@
 ///
+pub mod loader;
+///
+pub mod model_check;
+///
+pub mod property;
+///
+pub mod scenario;
+///
+pub mod suite;
+///
 pub mod test_case;
 ///
 pub mod util;
@@ -0,0 +1,317 @@
+use crate::error::BftError;
+use crate::whitebox::correctness::{
+    scenario::{Behavior, RoundPlan},
+    test_case::BftTest,
+};
+
+// The behaviors in the order of the canonical round plans.
+const BEHAVIORS: [Behavior; 4] = [
+    Behavior::Normal,
+    Behavior::Offline,
+    Behavior::Nil,
+    Behavior::Byzantine,
+];
+
+/// The config of model checking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelCheckConfig {
+    /// The max number of rounds in a sequence. All the sequences of 1 to `max_rounds`
+    /// rounds are enumerated.
+    pub max_rounds: usize,
+    /// Whether to enumerate the round plans by symmetry reduction. The simulated nodes
+    /// are interchangeable, so a round plan is determined by how many nodes take each
+    /// behavior. Turn it off to enumerate every assignment of behaviors to nodes.
+    pub symmetry: bool,
+    /// The max number of runs. If it is `None`, run all the sequences.
+    pub max_runs: Option<u64>,
+}
+
+impl Default for ModelCheckConfig {
+    fn default() -> Self {
+        ModelCheckConfig {
+            max_rounds: 1,
+            symmetry: true,
+            max_runs: None,
+        }
+    }
+}
+
+/// The expected outcome of a round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundExpect {
+    /// Whether the testing node should commit in the round.
+    pub commit: bool,
+    /// Whether the testing node should be locked at the end of the round. It is observed
+    /// by the proposal or the prevote of the testing node in the next round.
+    pub lock: bool,
+}
+
+/// The first failed sequence of model checking.
+#[derive(Debug)]
+pub struct Violation {
+    /// The index of the sequence in the enumeration.
+    pub index: u64,
+    /// The failed test case.
+    pub scenario: BftTest,
+    /// The expected outcomes of the rounds.
+    pub expects: Vec<RoundExpect>,
+    /// The error of the test case.
+    pub error: BftError,
+}
+
+/// The report of model checking.
+#[derive(Debug, Default)]
+pub struct ModelCheckReport {
+    /// The number of distinct round plans of a round.
+    pub round_plans: usize,
+    /// The number of sequences in the bounded space.
+    pub total: u64,
+    /// The number of sequences have been run.
+    pub runs: u64,
+    /// The number of sequences have passed.
+    pub passed: u64,
+    /// The number of rounds have been run that should commit.
+    pub commit_rounds: u64,
+    /// The number of rounds have been run that should not commit.
+    pub no_commit_rounds: u64,
+    /// The number of rounds have been run that should end with a lock.
+    pub lock_rounds: u64,
+    /// The first counterexample.
+    pub violation: Option<Violation>,
+}
+
+impl ModelCheckReport {
+    /// A function to get the coverage of the bounded space, from 0 to 1.
+    pub fn coverage(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.runs as f64 / self.total as f64
+    }
+
+    pub(crate) fn record(&mut self, expects: &[RoundExpect]) {
+        self.runs += 1;
+        for expect in expects.iter() {
+            if expect.commit {
+                self.commit_rounds += 1;
+            } else {
+                self.no_commit_rounds += 1;
+            }
+            if expect.lock {
+                self.lock_rounds += 1;
+            }
+        }
+    }
+}
+
+/// An enumerator of all the round plan sequences of 1 to `max_rounds` rounds. Shorter
+/// sequences come first.
+#[derive(Clone, Debug)]
+pub struct ModelChecker {
+    plans: Vec<RoundPlan>,
+    max_rounds: usize,
+    indexes: Vec<usize>,
+}
+
+impl ModelChecker {
+    /// A function to create a model checker for an authority list of `authority_num`.
+    pub fn new(authority_num: usize, config: &ModelCheckConfig) -> Self {
+        ModelChecker {
+            plans: round_plans(authority_num, config.symmetry),
+            max_rounds: config.max_rounds,
+            indexes: Vec::new(),
+        }
+    }
+
+    /// A function to get the distinct round plans of a round.
+    pub fn plans(&self) -> &[RoundPlan] {
+        &self.plans
+    }
+
+    /// A function to get the number of sequences in the bounded space.
+    pub fn total(&self) -> u64 {
+        let plans = self.plans.len() as u64;
+        (1..=self.max_rounds as u32).fold(0u64, |total, rounds| {
+            total.saturating_add(plans.saturating_pow(rounds))
+        })
+    }
+}
+
+impl Iterator for ModelChecker {
+    type Item = Vec<RoundPlan>;
+
+    fn next(&mut self) -> Option<Vec<RoundPlan>> {
+        if self.plans.is_empty() {
+            return None;
+        }
+        // increase the indexes like an odometer, and add a round when it overflows
+        let mut pos = self.indexes.len();
+        loop {
+            if pos == 0 {
+                if self.indexes.len() >= self.max_rounds {
+                    return None;
+                }
+                self.indexes = vec![0; self.indexes.len() + 1];
+                break;
+            }
+            pos -= 1;
+            self.indexes[pos] += 1;
+            if self.indexes[pos] < self.plans.len() {
+                break;
+            }
+            self.indexes[pos] = 0;
+        }
+        Some(
+            self.indexes
+                .iter()
+                .map(|index| self.plans[*index].clone())
+                .collect(),
+        )
+    }
+}
+
+/// A function to compute the expected outcomes of the rounds by the quorum rules. The
+/// testing node locks the proposal with +2/3 prevotes for it, and unlocks with +2/3 nil
+/// prevotes. The normal nodes precommit for the lock, and the testing node does only with
+/// +2/3 prevotes for the proposal in the round, so it commits with +2/3 precommits for the
+/// lock, and the lock is cleaned at the next height.
+pub fn expect_rounds(plans: &[RoundPlan]) -> Vec<RoundExpect> {
+    let mut lock = false;
+    let mut res = Vec::new();
+    for plan in plans.iter() {
+        let authority_num = plan.prevote.len() + 1;
+        let count = |votes: &[Behavior], behavior| votes.iter().filter(|b| **b == behavior).count();
+        let polka = (count(&plan.prevote, Behavior::Normal) + 1) * 3 > authority_num * 2;
+        if polka {
+            lock = true;
+        } else if count(&plan.prevote, Behavior::Nil) * 3 > authority_num * 2 {
+            lock = false;
+        }
+        let precommits = count(&plan.precommit, Behavior::Normal) + polka as usize;
+        let commit = lock && precommits * 3 > authority_num * 2;
+        res.push(RoundExpect { commit, lock });
+        if commit {
+            lock = false;
+        }
+    }
+    res
+}
+
+/// A function to enumerate the distinct round plans for an authority list of
+/// `authority_num`. With symmetry reduction, the behaviors of a vote step are sorted.
+/// The plans that a correct testing node can not precommit in are dropped, see
+/// `RoundPlan::can_precommit`.
+pub fn round_plans(authority_num: usize, symmetry: bool) -> Vec<RoundPlan> {
+    let votes = vote_behaviors(authority_num.saturating_sub(1), symmetry);
+    let mut res = Vec::with_capacity(votes.len() * votes.len());
+    for prevote in votes.iter() {
+        for precommit in votes.iter() {
+            let plan = RoundPlan::new(prevote.clone(), precommit.clone());
+            if plan.can_precommit() {
+                res.push(plan);
+            }
+        }
+    }
+    res
+}
+
+fn vote_behaviors(peers: usize, symmetry: bool) -> Vec<Vec<Behavior>> {
+    let mut res = vec![Vec::new()];
+    for _ in 0..peers {
+        let mut next = Vec::new();
+        for votes in res.into_iter() {
+            // with symmetry reduction, a behavior is not before the last one
+            let start = if symmetry {
+                votes
+                    .last()
+                    .and_then(|last| BEHAVIORS.iter().position(|b| b == last))
+                    .unwrap_or(0)
+            } else {
+                0
+            };
+            for behavior in BEHAVIORS[start..].iter() {
+                let mut votes = votes.clone();
+                votes.push(*behavior);
+                next.push(votes);
+            }
+        }
+        res = next;
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use Behavior::*;
+
+    #[test]
+    fn test_round_plans() {
+        // the prevotes of at most 1 offline node, by any precommits
+        assert_eq!(round_plans(4, false).len(), 54 * 64);
+        // the multisets of 3 behaviors of 4 kinds, with at most 1 offline prevote
+        assert_eq!(round_plans(4, true).len(), 16 * 20);
+        assert!(round_plans(4, true).iter().all(|plan| plan.can_precommit()));
+        assert_eq!(round_plans(1, true), vec![RoundPlan::new(vec![], vec![])]);
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let config = ModelCheckConfig {
+            max_rounds: 2,
+            ..ModelCheckConfig::default()
+        };
+        let checker = ModelChecker::new(2, &config);
+        assert_eq!(checker.plans().len(), 12);
+        assert_eq!(checker.total(), 12 + 12 * 12);
+
+        let sequences = checker.clone().collect::<Vec<_>>();
+        assert_eq!(sequences.len() as u64, checker.total());
+        assert_eq!(sequences[0].len(), 1);
+        assert_eq!(sequences[12].len(), 2);
+        let mut dedup = sequences.clone();
+        dedup.sort_by_key(|s| format!("{:?}", s));
+        dedup.dedup();
+        assert_eq!(dedup.len(), sequences.len());
+    }
+
+    #[test]
+    fn test_expect_rounds() {
+        let lock = RoundPlan::new(vec![Normal, Normal, Nil], vec![Nil, Nil, Nil]);
+        let keep = RoundPlan::new(vec![Offline, Byzantine, Nil], vec![Nil, Nil, Nil]);
+        let unlock = RoundPlan::new(vec![Nil, Nil, Nil], vec![Nil, Nil, Nil]);
+        let commit = RoundPlan::all(4, Normal, Normal);
+
+        let expects = expect_rounds(&[lock.clone(), keep.clone(), unlock, keep.clone()]);
+        assert_eq!(
+            expects.iter().map(|e| e.lock).collect::<Vec<_>>(),
+            vec![true, true, false, false]
+        );
+        assert!(expects.iter().all(|e| !e.commit));
+
+        // the normal nodes precommit for the lock without a polka in the round
+        let precommit = RoundPlan::new(vec![Normal, Nil, Offline], vec![Normal, Normal, Normal]);
+        let expects = expect_rounds(&[lock.clone(), precommit.clone()]);
+        assert_eq!(
+            expects.iter().map(|e| e.commit).collect::<Vec<_>>(),
+            vec![false, true]
+        );
+        let expects = expect_rounds(&[precommit]);
+        assert!(!expects[0].commit);
+
+        let expects = expect_rounds(&[commit, keep]);
+        assert_eq!(
+            expects,
+            vec![
+                RoundExpect {
+                    commit: true,
+                    lock: true
+                },
+                RoundExpect {
+                    commit: false,
+                    lock: false
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::error::BftError;
+use crate::whitebox::{
+    correctness::{
+        model_check::expect_rounds,
+        scenario::{Behavior, RoundPlan, Scenario},
+        test_case::BftTest,
+    },
+    types::FrameResult,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const OTHER_BEHAVIORS: [Behavior; 3] = [Behavior::Offline, Behavior::Byzantine, Behavior::Nil];
+const NORMAL_RATES: [f64; 4] = [0.5, 0.7, 0.9, 1.0];
+
+/// The config of a property-based test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyConfig {
+    /// The number of generated test cases.
+    pub cases: usize,
+    /// The max number of rounds in a generated test case.
+    pub max_rounds: usize,
+    /// The max number of test runs to shrink a failed test case.
+    pub max_shrinks: usize,
+}
+
+impl Default for PropertyConfig {
+    fn default() -> Self {
+        PropertyConfig {
+            cases: 100,
+            max_rounds: 8,
+            max_shrinks: 200,
+        }
+    }
+}
+
+/// A minimal failing test case found by a property-based test.
+#[derive(Debug)]
+pub struct Counterexample {
+    /// The index of the generated test case that failed first.
+    pub case: usize,
+    /// The seed of the property-based test.
+    pub seed: u64,
+    /// The number of rounds of the original failed test case.
+    pub original_rounds: usize,
+    /// The shrunk test case.
+    pub scenario: BftTest,
+    /// The error of the shrunk test case.
+    pub error: BftError,
+}
+
+/// A random scenario generator. Each generated scenario is a random sequence of rounds,
+/// and every round is followed by the expectation computed by the quorum rules, so a
+/// correct node should pass all of them. Every round has +2/3 prevotes that are not
+/// offline, see `RoundPlan::can_precommit`.
+#[derive(Clone, Debug)]
+pub struct ScenarioGen {
+    authority_num: usize,
+    max_rounds: usize,
+    rng: StdRng,
+}
+
+impl ScenarioGen {
+    /// A function to create a new scenario generator for an authority list of
+    /// `authority_num`. The same seed generates the same scenarios.
+    pub fn new(authority_num: usize, max_rounds: usize, seed: u64) -> Self {
+        ScenarioGen {
+            authority_num,
+            max_rounds: max_rounds.max(1),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// A function to generate the round plans of a scenario.
+    pub fn generate_plans(&mut self) -> Vec<RoundPlan> {
+        let rounds = self.rng.gen_range(1, self.max_rounds + 1);
+        (0..rounds).map(|_| self.generate_plan()).collect()
+    }
+
+    /// A function to generate a scenario. Return `FrameError::IllegalScenario` if the
+    /// authority list is too short.
+    pub fn generate(&mut self) -> FrameResult<BftTest> {
+        let plans = self.generate_plans();
+        scenario_from_plans(self.authority_num, &plans)
+    }
+
+    fn generate_plan(&mut self) -> RoundPlan {
+        // a round with a higher rate of normal nodes is more likely to commit
+        let rate = NORMAL_RATES[self.rng.gen_range(0, NORMAL_RATES.len())];
+        let peers = self.authority_num.saturating_sub(1);
+        let mut plan = RoundPlan::new(Vec::new(), Vec::new());
+        while plan.prevote.len() != peers || !plan.can_precommit() {
+            plan.prevote = (0..peers).map(|_| self.generate_behavior(rate)).collect();
+        }
+        plan.precommit = (0..peers).map(|_| self.generate_behavior(rate)).collect();
+        plan
+    }
+
+    fn generate_behavior(&mut self, rate: f64) -> Behavior {
+        if self.rng.gen_bool(rate) {
+            Behavior::Normal
+        } else {
+            OTHER_BEHAVIORS[self.rng.gen_range(0, OTHER_BEHAVIORS.len())]
+        }
+    }
+}
+
+/// A function to build a scenario from round plans. Each round is followed by the
+/// expectation computed by `expect_rounds`. Return `FrameError::IllegalScenario` if the
+/// plans do not fit the authority list.
+pub fn scenario_from_plans(authority_num: usize, plans: &[RoundPlan]) -> FrameResult<BftTest> {
+    let mut builder = Scenario::builder(authority_num);
+    for (plan, expect) in plans.iter().zip(expect_rounds(plans)) {
+        builder.plan(plan.clone());
+        if expect.commit {
+            builder.expect_commit();
+        } else {
+            builder.expect_no_commit();
+        }
+    }
+    builder.build()
+}
+
+/// A function to shrink a failed test case. The `run` function does a test and returns
+/// the error if it fails. The round plans are shrunk by removing rounds first, and then
+/// by replacing the behaviors with simpler ones, until no smaller test case fails or
+/// the `max_shrinks` runs are used up. A candidate with a round that a correct node
+/// cannot precommit in is skipped. Return the shrunk round plans and the error.
+pub fn shrink<F>(
+    authority_num: usize,
+    plans: Vec<RoundPlan>,
+    error: BftError,
+    max_shrinks: usize,
+    mut run: F,
+) -> (Vec<RoundPlan>, BftError)
+where
+    F: FnMut(&BftTest) -> Option<BftError>,
+{
+    let mut plans = plans;
+    let mut error = error;
+    let mut runs = 0;
+    let mut try_fail = |candidate: &[RoundPlan], runs: &mut usize| -> Option<BftError> {
+        if *runs >= max_shrinks || !candidate.iter().all(RoundPlan::can_precommit) {
+            return None;
+        }
+        let cases = scenario_from_plans(authority_num, candidate).ok()?;
+        *runs += 1;
+        run(&cases)
+    };
+
+    let mut progress = true;
+    while progress && runs < max_shrinks {
+        progress = false;
+
+        // remove rounds
+        let mut size = plans.len() / 2;
+        while size > 0 {
+            let mut start = 0;
+            while start + size <= plans.len() && plans.len() > size {
+                let mut candidate = plans.clone();
+                candidate.drain(start..start + size);
+                if let Some(e) = try_fail(&candidate, &mut runs) {
+                    plans = candidate;
+                    error = e;
+                    progress = true;
+                } else {
+                    start += size;
+                }
+            }
+            size /= 2;
+        }
+
+        // simplify behaviors
+        for index in 0..plans.len() {
+            for is_prevote in [true, false].iter() {
+                for peer in 0..authority_num.saturating_sub(1) {
+                    let current = vote_behavior(&plans[index], *is_prevote, peer);
+                    for simpler in simpler_behaviors(current).into_iter() {
+                        let mut candidate = plans.clone();
+                        set_vote_behavior(&mut candidate[index], *is_prevote, peer, simpler);
+                        if let Some(e) = try_fail(&candidate, &mut runs) {
+                            plans = candidate;
+                            error = e;
+                            progress = true;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    (plans, error)
+}
+
+fn vote_behavior(plan: &RoundPlan, is_prevote: bool, peer: usize) -> Behavior {
+    if is_prevote {
+        plan.prevote[peer]
+    } else {
+        plan.precommit[peer]
+    }
+}
+
+fn set_vote_behavior(plan: &mut RoundPlan, is_prevote: bool, peer: usize, behavior: Behavior) {
+    if is_prevote {
+        plan.prevote[peer] = behavior;
+    } else {
+        plan.precommit[peer] = behavior;
+    }
+}
+
+// The behaviors simpler than the given one, from the simplest.
+fn simpler_behaviors(behavior: Behavior) -> Vec<Behavior> {
+    let order = [
+        Behavior::Normal,
+        Behavior::Offline,
+        Behavior::Nil,
+        Behavior::Byzantine,
+    ];
+    order
+        .iter()
+        .take_while(|b| **b != behavior)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::correctness::scenario::Step;
+
+    #[test]
+    fn test_generate() {
+        for authority_num in [2, 4, 7, 10].iter() {
+            let mut gen = ScenarioGen::new(*authority_num, 6, 42);
+            for _ in 0..50 {
+                let scenario = gen.generate().unwrap();
+                assert_eq!(scenario.authority_num(), *authority_num);
+                let plans = scenario
+                    .iter()
+                    .filter_map(|step| match step {
+                        Step::Round(plan) => Some(plan.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let mut expects = expect_rounds(&plans).into_iter();
+                let mut commit_flag = false;
+                for step in scenario.iter() {
+                    match step {
+                        Step::Round(plan) => {
+                            assert!(plan.can_precommit());
+                            commit_flag = expects.next().unwrap().commit;
+                        }
+                        Step::ExpectCommit => assert!(commit_flag),
+                        Step::ExpectNoCommit => assert!(!commit_flag),
+                        Step::SkipRound => panic!("Generate skip round"),
+                    }
+                }
+            }
+        }
+
+        let mut gen_a = ScenarioGen::new(4, 6, 1);
+        let mut gen_b = ScenarioGen::new(4, 6, 1);
+        for _ in 0..10 {
+            assert_eq!(gen_a.generate().unwrap(), gen_b.generate().unwrap());
+        }
+        assert!(ScenarioGen::new(1, 6, 1).generate().is_err());
+    }
+
+    #[test]
+    fn test_shrink() {
+        // a fake node fails when a byzantine prevote follows a committed round
+        let fails = |scenario: &BftTest| -> Option<BftError> {
+            let mut committed = false;
+            for step in scenario.iter() {
+                if let Step::Round(plan) = step {
+                    if committed && plan.prevote.contains(&Behavior::Byzantine) {
+                        return Some(BftError::NoCommit(0));
+                    }
+                    committed = plan.should_commit();
+                }
+            }
+            None
+        };
+
+        let mut gen = ScenarioGen::new(7, 8, 7);
+        let plans = (0..200)
+            .map(|_| gen.generate_plans())
+            .find(|plans| fails(&scenario_from_plans(7, plans).unwrap()).is_some())
+            .unwrap();
+        let (shrunk, _) = shrink(7, plans, BftError::NoCommit(0), 1000, fails);
+
+        assert_eq!(shrunk.len(), 2);
+        assert_eq!(
+            shrunk[0],
+            RoundPlan::all(7, Behavior::Normal, Behavior::Normal)
+        );
+        assert_eq!(
+            shrunk[1]
+                .prevote
+                .iter()
+                .filter(|b| **b != Behavior::Normal)
+                .count(),
+            1
+        );
+        assert!(shrunk[1].precommit.iter().all(|b| *b == Behavior::Normal));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    correctness::util::{check_authority_num, fault_tolerance},
+    types::FrameResult,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// The behavior of a simulated node in a vote step.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Behavior {
+    /// The node does not send the vote.
+    Offline,
+    /// The node votes for the proposal.
+    Normal,
+    /// The node votes for a byzantine proposal.
+    Byzantine,
+    /// The node votes for nil.
+    Nil,
+}
+
+/// The behaviors of the simulated nodes in a round. Both of the prevote and the
+/// precommit behaviors are in the order of the authority list without the testing
+/// node, so their length should be the length of authority list minus one.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RoundPlan {
+    /// The prevote behaviors.
+    pub prevote: Vec<Behavior>,
+    /// The precommit behaviors.
+    pub precommit: Vec<Behavior>,
+}
+
+impl RoundPlan {
+    /// A function to create a new round plan.
+    pub fn new(prevote: Vec<Behavior>, precommit: Vec<Behavior>) -> Self {
+        RoundPlan { prevote, precommit }
+    }
+
+    /// A function to create a round plan that all the simulated nodes behave the same.
+    pub fn all(authority_num: usize, prevote: Behavior, precommit: Behavior) -> Self {
+        let peers = authority_num.saturating_sub(1);
+        RoundPlan {
+            prevote: vec![prevote; peers],
+            precommit: vec![precommit; peers],
+        }
+    }
+
+    /// A function to check whether a correct testing node can precommit in the round. It
+    /// waits for +2/3 prevotes of any proposal before it precommits, so the prevotes that
+    /// are not offline, with its own, should be above 2/3. Otherwise it never precommits
+    /// in the round, and no expectation of the round can be checked.
+    pub fn can_precommit(&self) -> bool {
+        let authority_num = self.prevote.len() + 1;
+        let live = self
+            .prevote
+            .iter()
+            .filter(|v| **v != Behavior::Offline)
+            .count()
+            + 1;
+        live * 3 > authority_num * 2
+    }
+
+    /// A function to check whether the testing node should commit in the round by the
+    /// quorum rules. The testing node is counted as a normal node, so it should commit
+    /// if both of the normal prevotes and the normal precommits are above 2/3. It is for
+    /// a round without a lock, see `expect_rounds` for the rounds of a sequence.
+    pub fn should_commit(&self) -> bool {
+        let authority_num = self.prevote.len() + 1;
+        let count =
+            |votes: &[Behavior]| votes.iter().filter(|v| **v == Behavior::Normal).count() + 1;
+        count(&self.prevote) * 3 > authority_num * 2
+            && count(&self.precommit) * 3 > authority_num * 2
+    }
+}
+
+/// A step of a scenario.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// Do a round with the plan. It should be followed by an expectation step.
+    Round(RoundPlan),
+    /// The testing node should commit in the previous round, then go to next height.
+    ExpectCommit,
+    /// The testing node should not commit in the previous round, then go to next round.
+    ExpectNoCommit,
+    /// Go to next round without sending any message. The testing node should jump to the
+    /// round of the next round step by `f + 1` votes of it, so the next round step should
+    /// have at least `f + 1` prevotes.
+    SkipRound,
+}
+
+/// A whitebox testing scenario for an authority list of a certain length.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scenario {
+    authority_num: usize,
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// A function to create a scenario builder for an authority list of `authority_num`.
+    pub fn builder(authority_num: usize) -> ScenarioBuilder {
+        ScenarioBuilder {
+            authority_num,
+            steps: Vec::new(),
+        }
+    }
+
+    /// A function to get the length of authority list of the scenario.
+    pub fn authority_num(&self) -> usize {
+        self.authority_num
+    }
+
+    /// A function to get the steps of the scenario.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// A function to iterate over the steps of the scenario.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, Step> {
+        self.steps.iter()
+    }
+}
+
+/// A scenario builder.
+#[derive(Clone, Debug)]
+pub struct ScenarioBuilder {
+    authority_num: usize,
+    steps: Vec<Step>,
+}
+
+impl ScenarioBuilder {
+    /// Append a round with the prevote and precommit behaviors.
+    pub fn round(&mut self, prevote: Vec<Behavior>, precommit: Vec<Behavior>) -> &mut Self {
+        self.plan(RoundPlan::new(prevote, precommit))
+    }
+
+    /// Append a round with the plan.
+    pub fn plan(&mut self, plan: RoundPlan) -> &mut Self {
+        self.step(Step::Round(plan))
+    }
+
+    /// Append an expectation that the testing node should commit.
+    pub fn expect_commit(&mut self) -> &mut Self {
+        self.step(Step::ExpectCommit)
+    }
+
+    /// Append an expectation that the testing node should not commit.
+    pub fn expect_no_commit(&mut self) -> &mut Self {
+        self.step(Step::ExpectNoCommit)
+    }
+
+    /// Append a round without any message.
+    pub fn skip_round(&mut self) -> &mut Self {
+        self.step(Step::SkipRound)
+    }
+
+    /// Append a step.
+    pub fn step(&mut self, step: Step) -> &mut Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Validate the steps and build the scenario. Return `FrameError::IllegalScenario`
+    /// if the authority list has less than 2 nodes, or the length of a round plan
+    /// mismatches the authority list, or a round is not followed by an expectation, or an
+    /// expectation does not follow a round.
+    pub fn build(&self) -> FrameResult<Scenario> {
+        check_authority_num(self.authority_num)?;
+
+        let peers = self.authority_num - 1;
+        let mut after_round = false;
+        let mut after_skip = false;
+        for (index, step) in self.steps.iter().enumerate() {
+            match step {
+                Step::Round(plan) => {
+                    if after_round {
+                        return Err(illegal_step(index, "round without expectation before"));
+                    }
+                    if plan.prevote.len() != peers || plan.precommit.len() != peers {
+                        return Err(illegal_step(
+                            index,
+                            &format!(
+                                "round plan length should be {:?}, get prevote {:?}, precommit {:?}",
+                                peers,
+                                plan.prevote.len(),
+                                plan.precommit.len()
+                            ),
+                        ));
+                    }
+                    let prevotes = plan
+                        .prevote
+                        .iter()
+                        .filter(|b| **b != Behavior::Offline)
+                        .count();
+                    if after_skip && prevotes <= fault_tolerance(self.authority_num) {
+                        return Err(illegal_step(
+                            index,
+                            &format!("round leap with {:?} prevotes", prevotes),
+                        ));
+                    }
+                    after_round = true;
+                    after_skip = false;
+                }
+                Step::ExpectCommit | Step::ExpectNoCommit => {
+                    if !after_round {
+                        return Err(illegal_step(index, "expectation without round before"));
+                    }
+                    after_round = false;
+                }
+                Step::SkipRound => {
+                    if after_round {
+                        return Err(illegal_step(index, "round without expectation before"));
+                    }
+                    after_skip = true;
+                }
+            }
+        }
+        if after_round {
+            return Err(illegal_step(
+                self.steps.len() - 1,
+                "round without expectation after",
+            ));
+        }
+
+        Ok(Scenario {
+            authority_num: self.authority_num,
+            steps: self.steps.clone(),
+        })
+    }
+}
+
+fn illegal_step(index: usize, reason: &str) -> FrameError {
+    FrameError::IllegalScenario(format!("step {:?}: {}", index, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_scenario() {
+        let scenario = Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .skip_round()
+            .round(
+                vec![Behavior::Normal, Behavior::Byzantine, Behavior::Offline],
+                vec![Behavior::Nil; 3],
+            )
+            .expect_no_commit()
+            .build()
+            .unwrap();
+        assert_eq!(scenario.authority_num(), 4);
+        assert_eq!(scenario.steps().len(), 5);
+        assert_eq!(scenario.steps()[1], Step::ExpectCommit);
+    }
+
+    #[test]
+    fn test_illegal_scenario() {
+        // plan length mismatch
+        assert!(Scenario::builder(7)
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .is_err());
+        // round without expectation
+        assert!(Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .is_err());
+        assert!(Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .build()
+            .is_err());
+        // expectation without round
+        assert!(Scenario::builder(4)
+            .skip_round()
+            .expect_no_commit()
+            .build()
+            .is_err());
+        // round leap without f + 1 prevotes
+        assert!(Scenario::builder(4)
+            .skip_round()
+            .round(
+                vec![Behavior::Normal, Behavior::Offline, Behavior::Offline],
+                vec![Behavior::Normal; 3],
+            )
+            .expect_no_commit()
+            .build()
+            .is_err());
+        assert!(Scenario::builder(0).build().is_err());
+        assert!(Scenario::builder(1)
+            .plan(RoundPlan::all(1, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use crate::error::{BftError, FrameError};
+use crate::whitebox::{
+    correctness::test_case::*,
+    types::{BftResult, FrameResult},
+};
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The fault type a test suite exercises.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum FaultType {
+    /// All the nodes are correct.
+    NoFault,
+    /// Some nodes are offline.
+    Offline,
+    /// Some nodes are byzantine.
+    Byzantine,
+    /// Some nodes are byzantine and some are offline.
+    Mixed,
+    /// The nodes lock and unlock proposals.
+    Lock,
+    /// The nodes skip rounds.
+    RoundLeap,
+    /// Other fault type defined by the downstream project.
+    Other(String),
+}
+
+type SuiteFn = Arc<dyn Fn(usize, u64) -> BftTest + Send + Sync>;
+
+/// A named test suite with metadata. The test case of a suite is generated by the
+/// authority number and the seed, and the same seed should generate the same test case.
+#[derive(Clone)]
+pub struct Suite {
+    name: String,
+    description: String,
+    tags: Vec<String>,
+    fault: FaultType,
+    expected_duration: Option<Duration>,
+    generate: SuiteFn,
+}
+
+impl Suite {
+    /// A function to create a suite with its name and the function to generate the test
+    /// case. The fault type is `FaultType::NoFault` by default.
+    pub fn new<F>(name: &str, generate: F) -> Self
+    where
+        F: Fn(usize, u64) -> BftTest + Send + Sync + 'static,
+    {
+        Suite {
+            name: name.to_string(),
+            description: String::new(),
+            tags: Vec::new(),
+            fault: FaultType::NoFault,
+            expected_duration: None,
+            generate: Arc::new(generate),
+        }
+    }
+
+    /// A function to set the description of the suite.
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// A function to set the tags of the suite.
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// A function to set the fault type the suite exercises.
+    pub fn with_fault(mut self, fault: FaultType) -> Self {
+        self.fault = fault;
+        self
+    }
+
+    /// A function to set the expected running time of the suite. A warning is logged if
+    /// the suite runs longer.
+    pub fn with_expected_duration(mut self, duration: Duration) -> Self {
+        self.expected_duration = Some(duration);
+        self
+    }
+
+    /// A function to get the name of the suite.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A function to get the description of the suite.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// A function to get the tags of the suite.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// A function to get the fault type the suite exercises.
+    pub fn fault(&self) -> &FaultType {
+        &self.fault
+    }
+
+    /// A function to get the expected running time of the suite. It is `None` if it is
+    /// unknown, which is the case of the built-in suites.
+    pub fn expected_duration(&self) -> Option<Duration> {
+        self.expected_duration
+    }
+
+    /// A function to generate the test case of the suite.
+    pub fn generate(&self, authority_num: usize, seed: u64) -> BftTest {
+        (self.generate)(authority_num, seed)
+    }
+
+    fn is_match(&self, selector: &SuiteSelector) -> bool {
+        match selector {
+            SuiteSelector::Name(pattern) => glob_match(pattern, &self.name),
+            SuiteSelector::Tag(tag) => self.tags.contains(tag),
+            SuiteSelector::Fault(fault) => self.fault == *fault,
+        }
+    }
+}
+
+impl fmt::Debug for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Suite")
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .field("tags", &self.tags)
+            .field("fault", &self.fault)
+            .field("expected_duration", &self.expected_duration)
+            .finish()
+    }
+}
+
+/// An ordered registry of test suites, which are run by `Actuator::run_suites` in the
+/// order of registration. The default registry has the built-in suites, and a downstream
+/// project can register its own suites after them.
+#[derive(Clone, Debug)]
+pub struct SuiteRegistry {
+    suites: Vec<Suite>,
+}
+
+impl SuiteRegistry {
+    /// A function to create an empty registry.
+    pub fn new() -> Self {
+        SuiteRegistry { suites: Vec::new() }
+    }
+
+    /// A function to register a suite. Return `FrameError::DuplicateSuite` if there is a
+    /// suite of the same name.
+    pub fn register(&mut self, suite: Suite) -> FrameResult<&mut Self> {
+        if self.get(&suite.name).is_some() {
+            return Err(FrameError::DuplicateSuite(suite.name));
+        }
+        self.suites.push(suite);
+        Ok(self)
+    }
+
+    /// A function to get a suite by its name.
+    pub fn get(&self, name: &str) -> Option<&Suite> {
+        self.suites.iter().find(|s| s.name == name)
+    }
+
+    /// A function to get all the suites in order.
+    pub fn suites(&self) -> &[Suite] {
+        &self.suites
+    }
+}
+
+impl Default for SuiteRegistry {
+    /// The registry of the built-in suites.
+    fn default() -> Self {
+        let suites = vec![
+            Suite::new("no_byzantine", no_byzantine_cases)
+                .with_description("All nodes are correct, commit in every round")
+                .with_tags(&["commit"]),
+            Suite::new("one_byzantine", one_byzantine_cases)
+                .with_description("f nodes vote for byzantine proposals")
+                .with_tags(&["byzantine"])
+                .with_fault(FaultType::Byzantine),
+            Suite::new("one_offline", one_offline_cases)
+                .with_description("f nodes are offline")
+                .with_tags(&["offline"])
+                .with_fault(FaultType::Offline),
+            Suite::new("two_byzantine", two_byzantine_cases)
+                .with_description("f + 1 nodes are byzantine, commit only with +2/3 votes")
+                .with_tags(&["byzantine"])
+                .with_fault(FaultType::Byzantine),
+            Suite::new("two_byzantine_one_offline", two_byzantine_one_offline)
+                .with_description("f + 1 nodes are byzantine and one node is offline")
+                .with_tags(&["byzantine", "offline"])
+                .with_fault(FaultType::Mixed),
+            Suite::new("lock_proposal", lock_proposal)
+                .with_description("Rounds without commit, then commit the locked proposal")
+                .with_tags(&["lock"])
+                .with_fault(FaultType::Lock),
+            Suite::new("round_leap", round_leap_cases)
+                .with_description("Skip rounds, the node jumps to the round by f + 1 votes")
+                .with_tags(&["round_leap"])
+                .with_fault(FaultType::RoundLeap),
+            Suite::new("proposal_with_lock", proposal_with_lock)
+                .with_description("Lock and unlock a proposal by PoLC between rounds")
+                .with_tags(&["lock"])
+                .with_fault(FaultType::Lock),
+        ];
+        SuiteRegistry { suites }
+    }
+}
+
+/// A selector of test suites.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SuiteSelector {
+    /// Select the suites whose names match the glob pattern, where `*` matches any
+    /// characters and `?` matches one character.
+    Name(String),
+    /// Select the suites with the tag.
+    Tag(String),
+    /// Select the suites that exercise the fault type.
+    Fault(FaultType),
+}
+
+/// The config of running test suites.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SuiteConfig {
+    /// Run only the suites that match any of the selectors. If it is empty, run all the
+    /// suites.
+    pub include: Vec<SuiteSelector>,
+    /// Skip the suites that match any of the selectors.
+    pub exclude: Vec<SuiteSelector>,
+    /// Whether to continue running the rest suites after a suite fails. Otherwise the
+    /// rest suites are skipped.
+    pub continue_on_failure: bool,
+}
+
+impl SuiteConfig {
+    /// A function to check whether the suite is selected to run.
+    pub fn is_selected(&self, suite: &Suite) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|s| suite.is_match(s)))
+            && !self.exclude.iter().any(|s| suite.is_match(s))
+    }
+}
+
+/// The outcome of a test suite.
+#[derive(Debug)]
+pub enum SuiteOutcome {
+    /// The suite passed.
+    Passed,
+    /// The suite failed with the error.
+    Failed(BftError),
+    /// The suite was not run.
+    Skipped,
+}
+
+/// The result of a test suite.
+#[derive(Debug)]
+pub struct SuiteResult {
+    /// The name of the suite.
+    pub name: String,
+    /// The tags of the suite.
+    pub tags: Vec<String>,
+    /// The outcome of the suite.
+    pub outcome: SuiteOutcome,
+    /// The running time of the suite.
+    pub duration: Duration,
+}
+
+/// The summary of running test suites, whose results are in the running order.
+#[derive(Debug, Default)]
+pub struct SuiteSummary {
+    /// The seed of the test cases.
+    pub seed: u64,
+    /// The results of the suites.
+    pub results: Vec<SuiteResult>,
+}
+
+impl SuiteSummary {
+    /// A function to get the names of the passed suites.
+    pub fn passed(&self) -> Vec<&str> {
+        self.names().0
+    }
+
+    /// A function to get the names of the failed suites.
+    pub fn failed(&self) -> Vec<&str> {
+        self.names().1
+    }
+
+    /// A function to get the names of the skipped suites.
+    pub fn skipped(&self) -> Vec<&str> {
+        self.names().2
+    }
+
+    /// A function to check whether no suite failed.
+    pub fn is_success(&self) -> bool {
+        self.failed().is_empty()
+    }
+
+    /// A function to turn the summary into the error of the first failed suite.
+    pub fn into_result(self) -> BftResult<()> {
+        for result in self.results.into_iter() {
+            if let SuiteOutcome::Failed(err) = result.outcome {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    // The names of the passed, the failed and the skipped suites.
+    fn names(&self) -> (Vec<&str>, Vec<&str>, Vec<&str>) {
+        let mut names = (Vec::new(), Vec::new(), Vec::new());
+        for result in self.results.iter() {
+            let name = result.name.as_str();
+            match result.outcome {
+                SuiteOutcome::Passed => names.0.push(name),
+                SuiteOutcome::Failed(_) => names.1.push(name),
+                SuiteOutcome::Skipped => names.2.push(name),
+            }
+        }
+        names
+    }
+}
+
+// Match the `name` with a glob `pattern`.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` and the matched name position
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((sp, sn)) = star {
+            p = sp + 1;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("two_*", "two_byzantine"));
+        assert!(glob_match("*_byzantine*", "two_byzantine_one_offline"));
+        assert!(glob_match("one_?ffline", "one_offline"));
+        assert!(!glob_match("two_*", "one_byzantine"));
+        assert!(!glob_match("one", "one_offline"));
+    }
+
+    #[test]
+    fn test_select() {
+        let config = SuiteConfig {
+            include: vec![
+                SuiteSelector::Tag("byzantine".to_string()),
+                SuiteSelector::Name("no_*".to_string()),
+            ],
+            exclude: vec![SuiteSelector::Tag("offline".to_string())],
+            continue_on_failure: false,
+        };
+        let registry = SuiteRegistry::default();
+        let selected = registry
+            .suites()
+            .iter()
+            .filter(|s| config.is_selected(s))
+            .map(|s| s.name())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selected,
+            vec!["no_byzantine", "one_byzantine", "two_byzantine"]
+        );
+        assert!(registry
+            .suites()
+            .iter()
+            .all(|s| SuiteConfig::default().is_selected(s)));
+
+        let config = SuiteConfig {
+            include: vec![SuiteSelector::Fault(FaultType::Lock)],
+            ..SuiteConfig::default()
+        };
+        assert_eq!(
+            registry
+                .suites()
+                .iter()
+                .filter(|s| config.is_selected(s))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = SuiteRegistry::default();
+        let len = registry.suites().len();
+        let suite = Suite::new("custom", |authority_num, seed| {
+            lock_proposal(authority_num, seed + 1)
+        })
+        .with_description("A custom suite")
+        .with_fault(FaultType::Other("custom".to_string()))
+        .with_expected_duration(Duration::from_secs(1));
+        registry.register(suite.clone()).unwrap();
+        assert_eq!(registry.suites().len(), len + 1);
+        assert_eq!(registry.suites()[len].name(), "custom");
+        assert_eq!(
+            registry.get("custom").unwrap().generate(4, 0),
+            lock_proposal(4, 1)
+        );
+        assert!(registry.register(suite).is_err());
+        assert!(SuiteRegistry::new().suites().is_empty());
+    }
+}
@@ -1,214 +1,273 @@
-use crate::whitebox::correctness::util::*;
-use rand::random;
-use std::collections::HashMap;
+use crate::whitebox::correctness::{
+    scenario::{Behavior::*, RoundPlan, Scenario, ScenarioBuilder, Step},
+    util::*,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 // use std::time::Duration;
 
 /// A basic test unit.
-pub type BftTestUnit = [u8; 6];
+pub type BftTestUnit = Step;
 /// A BFT test case.
-pub type BftTest = Vec<BftTestUnit>;
-
-pub(crate) const OFFLINE: u8 = 0;
-pub(crate) const NORMAL: u8 = 1;
-pub(crate) const BYZANTINE: u8 = 2;
-pub(crate) const NIL: u8 = 3;
-pub(crate) const NULL_ROUND: [u8; 6] = [7, 7, 7, 7, 7, 7];
-pub(crate) const SHOULD_COMMIT: [u8; 6] = [8, 8, 8, 8, 8, 8];
-pub(crate) const SHOULD_NOT_COMMIT: [u8; 6] = [9, 9, 9, 9, 9, 9];
-
-pub(crate) fn byzantine_proposal() -> Vec<Vec<u8>> {
-    vec![
-        vec![0, 0, 0, 0, 0, 0],
-        vec![1, 1, 1, 1, 1, 1],
-        vec![2, 2, 2, 2, 2, 2],
-    ]
+pub type BftTest = Scenario;
+
+// The byzantine proposals are of 8 bytes, so they are distinct for any authority number
+// and never equal to the 6 bytes proposals of the correct nodes.
+pub(crate) fn byzantine_proposal(authority_num: usize) -> Vec<Vec<u8>> {
+    (0..authority_num.max(2) - 1)
+        .map(|index| (index as u64).to_be_bytes().to_vec())
+        .collect()
+}
+
+fn build(builder: &ScenarioBuilder) -> BftTest {
+    builder.build().expect("Illegal built-in test case!")
 }
 
-/// A function to generate no byzantine test cases.
-pub fn no_byzantine_cases() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate no byzantine test cases. The cases are fixed, so the `seed`
+/// is unused.
+pub fn no_byzantine_cases(authority_num: usize, _seed: u64) -> BftTest {
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..100 {
-        cases.push([1, 1, 1, 1, 1, 1]);
-        cases.push(SHOULD_COMMIT);
+        cases
+            .plan(RoundPlan::all(authority_num, Normal, Normal))
+            .expect_commit();
     }
-    cases
+    build(&cases)
 }
 
-/// A function to generate one node offline test cases.
-pub fn one_offline_cases() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate `f` nodes offline test cases. The same `seed` generates the
+/// same cases.
+pub fn one_offline_cases(authority_num: usize, seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..100 {
-        cases.push(rand_attribute(OFFLINE, NORMAL));
-        cases.push(SHOULD_COMMIT);
+        cases
+            .plan(rand_attribute(&mut rng, authority_num, f, Offline, Normal))
+            .expect_commit();
     }
-    cases
+    build(&cases)
 }
 
-/// A function to generate one byzantine node test cases.
-pub fn one_byzantine_cases() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate `f` byzantine nodes test cases. The same `seed` generates
+/// the same cases.
+pub fn one_byzantine_cases(authority_num: usize, seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..100 {
-        cases.push(rand_attribute(BYZANTINE, NORMAL));
-        cases.push(SHOULD_COMMIT);
+        cases
+            .plan(rand_attribute(
+                &mut rng,
+                authority_num,
+                f,
+                Byzantine,
+                Normal,
+            ))
+            .expect_commit();
     }
-    cases
+    build(&cases)
 }
 
-/// A function to generate two byzantine node test cases.
-pub fn two_byzantine_cases() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate `f + 1` byzantine nodes test cases. The same `seed` generates
+/// the same cases.
+pub fn two_byzantine_cases(authority_num: usize, seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..20 {
-        cases.push(rand_two_attribute(BYZANTINE, NORMAL, NORMAL));
-        cases.push(SHOULD_NOT_COMMIT);
+        cases
+            .plan(rand_two_attribute(
+                &mut rng,
+                authority_num,
+                f + 1,
+                Byzantine,
+                Normal,
+                Normal,
+            ))
+            .expect_no_commit();
     }
-    cases.push([1, 1, 1, 1, 1, 1]);
-    cases.push(SHOULD_COMMIT);
     cases
+        .plan(RoundPlan::all(authority_num, Normal, Normal))
+        .expect_commit();
+    build(&cases)
 }
 
-/// A function to generate two byzantine node and one offline node test cases.
-pub fn two_byzantine_one_offline() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate `f + 1` byzantine nodes and one offline node test cases. The
+/// other nodes vote for nil, so the testing node gets no +2/3 prevotes and should not
+/// commit until the last round. The same `seed` generates the same cases.
+pub fn two_byzantine_one_offline(authority_num: usize, seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..10 {
-        cases.push(rand_two_attribute(BYZANTINE, OFFLINE, NIL));
-        cases.push(SHOULD_NOT_COMMIT);
+        let mut plan = rand_two_attribute(&mut rng, authority_num, f + 1, Byzantine, Nil, Nil);
+        let others = (0..plan.prevote.len())
+            .filter(|i| plan.prevote[*i] != Byzantine)
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            plan.prevote[others[rng.gen_range(0, others.len())]] = Offline;
+        }
+        cases.plan(plan).expect_no_commit();
     }
-    cases.push([1, 1, 1, 1, 1, 1]);
-    cases.push(SHOULD_COMMIT);
     cases
+        .plan(RoundPlan::all(authority_num, Normal, Normal))
+        .expect_commit();
+    build(&cases)
 }
 
-/// A function to generate round leap test cases.
-pub fn round_leap_cases() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate round leap test cases. The cases are fixed, so the `seed` is
+/// unused.
+pub fn round_leap_cases(authority_num: usize, _seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let normal = (authority_num - f).saturating_sub(2);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..3 {
-        cases.push([1, 2, 2, 3, 3, 3]);
-        cases.push(SHOULD_NOT_COMMIT);
-        cases.push(NULL_ROUND);
-        cases.push(NULL_ROUND);
-        cases.push(NULL_ROUND);
-        cases.push([1, 1, 1, 1, 1, 1]);
-        cases.push(SHOULD_COMMIT);
+        cases
+            .round(
+                fill_attribute(authority_num, normal, &[Byzantine]),
+                fill_attribute(authority_num, 0, &[Nil]),
+            )
+            .expect_no_commit()
+            .skip_round()
+            .skip_round()
+            .skip_round()
+            .plan(RoundPlan::all(authority_num, Normal, Normal))
+            .expect_commit();
     }
-    cases
+    build(&cases)
 }
 
-/// A function to generate proposal with lock test cases.
-pub fn lock_proposal() -> BftTest {
-    let mut cases = Vec::new();
+/// A function to generate proposal with lock test cases. The same `seed` generates the
+/// same cases.
+pub fn lock_proposal(authority_num: usize, seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let normal = (authority_num - f).saturating_sub(2);
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..10 {
-        if random::<bool>() {
-            cases.push([1, 1, 1, 1, 0, 2]);
+        if rng.gen::<bool>() {
+            cases.round(
+                fill_attribute(authority_num, authority_num.saturating_sub(1), &[]),
+                fill_attribute(authority_num, normal, &[Offline, Byzantine]),
+            );
         } else {
-            cases.push([1, 2, 0, 1, 2, 0]);
+            cases.round(
+                fill_attribute(authority_num, normal, &[Byzantine, Offline]),
+                fill_attribute(authority_num, normal, &[Byzantine, Offline]),
+            );
         }
-        cases.push(SHOULD_NOT_COMMIT);
+        cases.expect_no_commit();
     }
-    cases.push([1, 1, 1, 1, 1, 1]);
-    cases.push(SHOULD_COMMIT);
     cases
+        .plan(RoundPlan::all(authority_num, Normal, Normal))
+        .expect_commit();
+    build(&cases)
 }
 
-///  A function to generate proposal with lock test cases.
-pub fn proposal_with_lock() -> BftTest {
-    let mut cases = Vec::new();
+///  A function to generate proposal with lock test cases. The cases are fixed, so the
+/// `seed` is unused.
+pub fn proposal_with_lock(authority_num: usize, _seed: u64) -> BftTest {
+    let f = fault_tolerance(authority_num);
+    let normal = (authority_num - f).saturating_sub(2);
+    let no_lock = RoundPlan::new(
+        fill_attribute(authority_num, normal, &[Nil, Offline]),
+        fill_attribute(authority_num, normal, &[Nil]),
+    );
+    let lock = RoundPlan::new(
+        fill_attribute(authority_num, normal + 1, &[Nil]),
+        fill_attribute(authority_num, normal, &[Nil]),
+    );
+    let mut cases = Scenario::builder(authority_num);
     for _ in 0..10 {
-        cases.push([1, 3, 0, 1, 3, 3]);
-        cases.push(SHOULD_NOT_COMMIT);
-        cases.push([1, 3, 1, 1, 3, 3]);
-        cases.push(SHOULD_NOT_COMMIT);
-        cases.push([1, 3, 0, 1, 3, 3]);
-        cases.push(SHOULD_NOT_COMMIT);
-        cases.push([0, 3, 1, 1, 3, 3]);
-        cases.push(SHOULD_NOT_COMMIT);
-        cases.push([1, 1, 1, 1, 1, 1]);
-        cases.push(SHOULD_COMMIT);
+        cases
+            .plan(no_lock.clone())
+            .expect_no_commit()
+            .plan(lock.clone())
+            .expect_no_commit()
+            .plan(no_lock.clone())
+            .expect_no_commit()
+            .plan(no_lock.clone())
+            .expect_no_commit()
+            .plan(RoundPlan::all(authority_num, Normal, Normal))
+            .expect_commit();
     }
-    cases
+    build(&cases)
 }
 
-pub(crate) fn all_cases() -> HashMap<String, BftTest> {
-    let mut test_cases = HashMap::new();
-
-    test_cases
-        .entry("test no byzantine case".to_string())
-        .or_insert_with(no_byzantine_cases);
-    test_cases
-        .entry("test one byzantine case".to_string())
-        .or_insert_with(one_byzantine_cases);
-    test_cases
-        .entry("test one offline case".to_string())
-        .or_insert_with(one_offline_cases);
-    test_cases
-        .entry("test two byzantine case".to_string())
-        .or_insert_with(two_byzantine_cases);
-    test_cases
-        .entry("test two byzantine and one offline case".to_string())
-        .or_insert_with(two_byzantine_one_offline);
-    test_cases
-        .entry("test lock proposal".to_string())
-        .or_insert_with(lock_proposal);
-    test_cases
+// The test cases of the default suites in the running order.
+#[cfg(test)]
+pub(crate) fn all_cases(authority_num: usize, seed: u64) -> Vec<(String, BftTest)> {
+    crate::whitebox::correctness::suite::SuiteRegistry::default()
+        .suites()
+        .iter()
+        .map(|suite| {
+            (
+                suite.name().to_string(),
+                suite.generate(authority_num, seed),
+            )
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    fn should_commit(mut prevote: Vec<u8>, mut precommit: Vec<u8>) -> bool {
-        prevote.push(1);
-        precommit.push(1);
-        let mut prevote_count: usize = 0;
-        let mut precommit_count: usize = 0;
-        for v in prevote.iter() {
-            if v == &1 {
-                prevote_count += 1;
-            }
-        }
-        if prevote_count >= 3 {
-            for v in precommit.iter() {
-                if v == &1 {
-                    precommit_count += 1
-                }
-            }
-            if precommit_count >= 3 {
-                return true;
-            }
-        }
-        false
-    }
-
     #[test]
     fn test_devide() {
-        let unit: BftTestUnit = [1, 1, 1, 2, 2, 2];
-        let cases: BftTest = vec![unit];
+        let unit: BftTestUnit = Step::Round(RoundPlan::new(vec![Normal; 3], vec![Nil; 3]));
+        let cases: BftTest = Scenario::builder(4)
+            .step(unit)
+            .expect_no_commit()
+            .build()
+            .unwrap();
         for case in cases.iter() {
-            let prevote = case[0..3].to_vec();
-            let precommit = case[3..6].to_vec();
-            assert_eq!(prevote, vec![1, 1, 1]);
-            assert_eq!(precommit, vec![2, 2, 2]);
+            if let Step::Round(plan) = case {
+                assert_eq!(plan.prevote, vec![Normal, Normal, Normal]);
+                assert_eq!(plan.precommit, vec![Nil, Nil, Nil]);
+            }
         }
     }
 
     #[test]
     fn test_cases_retional() {
-        let all_test_cases = all_cases();
-        let mut commit_flag: bool = true;
-        for (test_name, test_case) in all_test_cases.into_iter() {
-            println!("Test retional of {:?}", test_name);
-            for case in test_case.iter() {
-                if case == &SHOULD_COMMIT || case == &SHOULD_NOT_COMMIT {
-                    if commit_flag {
-                        assert_eq!(case.to_vec(), SHOULD_COMMIT);
-                    } else {
-                        assert_eq!(case.to_vec(), SHOULD_NOT_COMMIT);
+        for (authority_num, seed) in [2, 3, 4, 7, 10, 21].iter().zip(0..) {
+            let all_test_cases = all_cases(*authority_num, seed);
+            let mut commit_flag: bool = true;
+            for (test_name, test_case) in all_test_cases.into_iter() {
+                println!(
+                    "Test retional of {:?} with {:?} nodes, seed {:?}",
+                    test_name, authority_num, seed
+                );
+                assert_eq!(test_case.authority_num(), *authority_num);
+                for case in test_case.iter() {
+                    match case {
+                        Step::Round(plan) => commit_flag = plan.should_commit(),
+                        Step::ExpectCommit => assert!(commit_flag),
+                        Step::ExpectNoCommit => assert!(!commit_flag),
+                        Step::SkipRound => (),
                     }
-                } else if case != &NULL_ROUND {
-                    let prevote = case[0..3].to_vec();
-                    let precommit = case[3..6].to_vec();
-                    commit_flag = should_commit(prevote, precommit);
                 }
             }
         }
     }
+
+    #[test]
+    fn test_seed() {
+        for (authority_num, seed) in [(4, 3), (7, 42)].iter().cloned() {
+            assert_eq!(
+                all_cases(authority_num, seed),
+                all_cases(authority_num, seed)
+            );
+        }
+        assert_ne!(one_offline_cases(10, 1), one_offline_cases(10, 2));
+    }
+
+    #[test]
+    fn test_byzantine_proposal() {
+        let proposals = byzantine_proposal(300);
+        assert_eq!(proposals.len(), 299);
+        let distinct = proposals.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(distinct.len(), proposals.len());
+    }
 }
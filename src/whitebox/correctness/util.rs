@@ -1,54 +1,127 @@
-use crate::whitebox::correctness::test_case::BftTestUnit;
-use rand::{thread_rng, Rng};
-
-pub(crate) fn rand_attribute(attri: u8, base: u8) -> BftTestUnit {
-    let mut rng = thread_rng();
-    let index_1: usize = rng.gen_range(0, 3);
-    let index_2: usize = rng.gen_range(3, 6);
-    let mut unit = [base; 6];
-    for (index, item) in unit.iter_mut().enumerate() {
-        if index == index_1 || index == index_2 {
-            *item = attri;
-        }
+use crate::error::FrameError;
+use crate::whitebox::{
+    correctness::scenario::{Behavior, RoundPlan},
+    types::FrameResult,
+};
+use rand::{seq::sample_indices, Rng};
+
+// The testing node needs at least one simulated node to vote with it.
+const MIN_AUTHORITY_NUM: usize = 2;
+
+/// Check the length of an authority list, which should be at least 2.
+pub(crate) fn check_authority_num(authority_num: usize) -> FrameResult<()> {
+    if authority_num < MIN_AUTHORITY_NUM {
+        return Err(FrameError::IllegalScenario(format!(
+            "authority list of {:?} nodes, at least {:?} are required",
+            authority_num, MIN_AUTHORITY_NUM
+        )));
     }
-    unit
+    Ok(())
+}
+
+/// The maximum number of faulty nodes `f` that an authority list of
+/// `authority_num` nodes can tolerate.
+pub(crate) fn fault_tolerance(authority_num: usize) -> usize {
+    authority_num.saturating_sub(1) / 3
+}
+
+pub(crate) fn rand_attribute<R: Rng>(
+    rng: &mut R,
+    authority_num: usize,
+    count: usize,
+    attri: Behavior,
+    base: Behavior,
+) -> RoundPlan {
+    rand_two_attribute(rng, authority_num, count, attri, base, base)
 }
 
-pub(crate) fn rand_two_attribute(attri: u8, base_prevote: u8, base_precommit: u8) -> BftTestUnit {
-    let mut rng = thread_rng();
-    let index_1: usize = rng.gen_range(0, 3);
-    let index_2: usize = rng.gen_range(3, 6);
-    let mut unit = [
-        base_prevote,
-        base_prevote,
-        base_prevote,
-        base_precommit,
-        base_precommit,
-        base_precommit,
-    ];
-    for (index, item) in unit.iter_mut().enumerate() {
-        if index != index_1 && index != index_2 {
-            *item = attri;
-        }
+pub(crate) fn rand_two_attribute<R: Rng>(
+    rng: &mut R,
+    authority_num: usize,
+    count: usize,
+    attri: Behavior,
+    base_prevote: Behavior,
+    base_precommit: Behavior,
+) -> RoundPlan {
+    let mut plan = RoundPlan::all(authority_num, base_prevote, base_precommit);
+    let peers = plan.prevote.len();
+    for index in sample_indices(rng, peers, count).into_iter() {
+        plan.prevote[index] = attri;
     }
-    unit
+    for index in sample_indices(rng, peers, count).into_iter() {
+        plan.precommit[index] = attri;
+    }
+    plan
+}
+
+/// Build the behaviors of one step for the nodes except the testing node. The first
+/// `normal` nodes are normal and the rest take the `others` behaviors by turn.
+pub(crate) fn fill_attribute(
+    authority_num: usize,
+    normal: usize,
+    others: &[Behavior],
+) -> Vec<Behavior> {
+    (0..authority_num.saturating_sub(1))
+        .map(|index| {
+            if index < normal {
+                Behavior::Normal
+            } else {
+                others[(index - normal) % others.len()]
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
 
-    fn sum(a: [u8; 6]) -> usize {
-        let mut sum = 0;
-        for i in 0..6 {
-            sum += a[i] as usize;
-        }
-        sum
+    fn count(a: &[Behavior], b: Behavior) -> usize {
+        a.iter().filter(|i| **i == b).count()
     }
 
     #[test]
     fn unit_test() {
-        assert_eq!(sum(rand_attribute(0, 1)), 4);
-        assert_eq!(sum(rand_two_attribute(0, 1, 1)), 2);
+        let mut rng = StdRng::seed_from_u64(0);
+        let plan = rand_attribute(&mut rng, 4, 1, Behavior::Offline, Behavior::Normal);
+        assert_eq!(count(&plan.prevote, Behavior::Normal), 2);
+        assert_eq!(count(&plan.precommit, Behavior::Normal), 2);
+        let plan = rand_two_attribute(
+            &mut rng,
+            4,
+            2,
+            Behavior::Byzantine,
+            Behavior::Offline,
+            Behavior::Nil,
+        );
+        assert_eq!(count(&plan.prevote, Behavior::Byzantine), 2);
+        assert_eq!(count(&plan.prevote, Behavior::Offline), 1);
+        assert_eq!(count(&plan.precommit, Behavior::Nil), 1);
+        let plan = rand_attribute(&mut rng, 7, 2, Behavior::Offline, Behavior::Normal);
+        assert_eq!(count(&plan.prevote, Behavior::Normal), 4);
+        assert_eq!(
+            rand_attribute(&mut rng, 10, 3, Behavior::Nil, Behavior::Normal)
+                .precommit
+                .len(),
+            9
+        );
+    }
+
+    #[test]
+    fn test_fault_tolerance() {
+        assert_eq!(fault_tolerance(4), 1);
+        assert_eq!(fault_tolerance(7), 2);
+        assert_eq!(fault_tolerance(10), 3);
+        assert_eq!(fault_tolerance(21), 6);
+        assert_eq!(
+            fill_attribute(5, 2, &[Behavior::Nil, Behavior::Offline]),
+            vec![
+                Behavior::Normal,
+                Behavior::Normal,
+                Behavior::Nil,
+                Behavior::Offline
+            ]
+        );
     }
 }
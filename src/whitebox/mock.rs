@@ -0,0 +1,855 @@
+use crate::whitebox::{
+    reference::{can_prevote, is_polc, LockChange},
+    types::*,
+};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A correct mock consensus node for whitebox testing. It is a Tendermint-style state
+/// machine without timer. The actuator always sends all the messages of a step before it
+/// waits for the output of the testing node, so the mock node takes an empty output
+/// queue in `recv` as the timeout of the current step. It moves to the next round when it
+/// receives a proposal of the round, and leaps to a later round only when it receives
+/// votes of the round from `f + 1` authorities. When it receives a feed after it has
+/// voted in the current round, it goes to its next proposing round.
+#[derive(Clone, Debug)]
+pub struct MockNode {
+    state: Arc<Mutex<MockState>>,
+}
+
+/// A deliberate bug of a faulty mock node. The actuator should catch each of them.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Fault {
+    /// Precommit the proposal right after prevote, without waiting for +2/3 prevotes.
+    PrecommitWithoutQuorum,
+    /// Never lock a proposal.
+    IgnoreLock,
+    /// Commit every height twice.
+    DoubleCommit,
+    /// Prevote for both of the proposal and nil.
+    Equivocate,
+    /// Propose the locked proposal with a wrong lock round.
+    WrongLockRound,
+    /// Commit with any precommit for the proposal, without waiting for +2/3 precommits.
+    CommitWithoutQuorum,
+    /// Always prevote nil.
+    PrevoteNil,
+}
+
+impl Fault {
+    /// A function to get all the faults.
+    pub fn all() -> Vec<Fault> {
+        vec![
+            Fault::PrecommitWithoutQuorum,
+            Fault::IgnoreLock,
+            Fault::DoubleCommit,
+            Fault::Equivocate,
+            Fault::WrongLockRound,
+            Fault::CommitWithoutQuorum,
+            Fault::PrevoteNil,
+        ]
+    }
+}
+
+impl MockNode {
+    /// A function to create a new mock node with its address. The node starts after it
+    /// receives a status.
+    pub fn new(address: Address) -> Self {
+        MockNode {
+            state: Arc::new(Mutex::new(MockState::new(address, None))),
+        }
+    }
+
+    /// A function to create a faulty mock node with the `fault`.
+    pub fn faulty(address: Address, fault: Fault) -> Self {
+        MockNode {
+            state: Arc::new(Mutex::new(MockState::new(address, Some(fault)))),
+        }
+    }
+}
+
+impl Support for MockNode {
+    fn send(&self, msg: FrameSend) {
+        let mut state = self.state.lock().unwrap();
+        match msg {
+            FrameSend::Proposal(p) => state.handle_proposal(p),
+            FrameSend::Vote(v) => state.handle_vote(v),
+            FrameSend::Feed(f) => state.handle_feed(f),
+            FrameSend::Status(s) => state.handle_status(s),
+        }
+    }
+
+    fn recv(&self) -> FrameRecv {
+        self.recv_timeout(Duration::from_secs(0))
+            .unwrap_or_else(|| panic!("Mock node is waiting for more messages"))
+    }
+
+    fn recv_timeout(&self, _timeout: Duration) -> Option<FrameRecv> {
+        // the mock node never sends messages later, so it does not wait
+        let mut state = self.state.lock().unwrap();
+        if state.outbox.is_empty() {
+            state.timeout();
+        }
+        state.outbox.pop_front()
+    }
+
+    fn try_get_commit(&self) -> Option<Commit> {
+        self.state.lock().unwrap().commits.pop_front()
+    }
+
+    fn stop(&self) {}
+
+    fn cal_proposer(&self, height: u64, round: u64) -> usize {
+        let state = self.state.lock().unwrap();
+        state.proposer(height, round)
+    }
+
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let address = state.address.clone();
+        let fault = state.fault;
+        *state = MockState::new(address, fault);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum MockStep {
+    Propose,
+    Prevote,
+    Precommit,
+}
+
+#[derive(Debug)]
+struct MockState {
+    address: Address,
+    fault: Option<Fault>,
+    authority_list: Vec<Address>,
+    height: u64,
+    round: u64,
+    step: MockStep,
+    committed: bool,
+    feed: Option<Vec<u8>>,
+    lock: Option<(u64, Vec<u8>, Vec<Vote>)>,
+    proposals: HashMap<u64, Proposal>,
+    // (round, vote type) -> voter -> proposal
+    votes: HashMap<(u64, VoteType), HashMap<Address, Vec<u8>>>,
+    outbox: VecDeque<FrameRecv>,
+    commits: VecDeque<Commit>,
+}
+
+impl MockState {
+    fn new(address: Address, fault: Option<Fault>) -> Self {
+        MockState {
+            address,
+            fault,
+            authority_list: Vec::new(),
+            height: 0,
+            round: 0,
+            step: MockStep::Propose,
+            committed: false,
+            feed: None,
+            lock: None,
+            proposals: HashMap::new(),
+            votes: HashMap::new(),
+            outbox: VecDeque::new(),
+            commits: VecDeque::new(),
+        }
+    }
+
+    fn proposer(&self, height: u64, round: u64) -> usize {
+        if self.authority_list.is_empty() {
+            return 0;
+        }
+        ((height + round) % self.authority_list.len() as u64) as usize
+    }
+
+    fn is_proposer(&self, round: u64) -> bool {
+        self.authority_list.get(self.proposer(self.height, round)) == Some(&self.address)
+    }
+
+    fn is_above_threshold(&self, num: usize) -> bool {
+        num * 3 > self.authority_list.len() * 2
+    }
+
+    fn handle_status(&mut self, status: Status) {
+        if status.height + 1 < self.height {
+            return;
+        }
+        self.authority_list = status.authority_list;
+        self.height = status.height + 1;
+        self.committed = false;
+        self.feed = None;
+        self.lock = None;
+        self.proposals.clear();
+        self.votes.clear();
+        self.goto_round(0);
+    }
+
+    fn handle_feed(&mut self, feed: Feed) {
+        if feed.height != self.height {
+            return;
+        }
+        self.feed = Some(feed.proposal);
+        // before the node votes in the current round, the feed is kept for its proposal
+        if self.step == MockStep::Propose && !self.is_proposer(self.round) {
+            return;
+        }
+        // otherwise the rounds before the next proposing round are timeout
+        let mut round = self.round;
+        if self.step != MockStep::Propose {
+            round += 1;
+        }
+        while !self.is_proposer(round) {
+            round += 1;
+        }
+        if round != self.round {
+            self.round = round;
+            self.step = MockStep::Propose;
+        }
+        self.propose();
+    }
+
+    fn handle_proposal(&mut self, proposal: Proposal) {
+        if proposal.height != self.height
+            || proposal.round < self.round
+            || self
+                .authority_list
+                .get(self.proposer(proposal.height, proposal.round))
+                != Some(&proposal.proposer)
+        {
+            return;
+        }
+        let round = proposal.round;
+        self.proposals.entry(round).or_insert(proposal);
+        if round == self.round + 1 {
+            // the proposal of the next round means the current round is timeout, while
+            // a proposal of a later round waits for f + 1 votes of the round
+            self.goto_round(round);
+        } else if round == self.round {
+            self.prevote();
+        }
+    }
+
+    fn handle_vote(&mut self, vote: Vote) {
+        if vote.height != self.height || !self.authority_list.contains(&vote.voter) {
+            return;
+        }
+        let round = vote.round;
+        self.votes
+            .entry((round, vote.vote_type.clone()))
+            .or_default()
+            .entry(vote.voter)
+            .or_insert(vote.proposal);
+
+        if round > self.round {
+            // f + 1 votes of a later round
+            let voters = [VoteType::Prevote, VoteType::Precommit]
+                .iter()
+                .filter_map(|t| self.votes.get(&(round, t.clone())))
+                .flat_map(|votes| votes.keys())
+                .collect::<HashSet<_>>()
+                .len();
+            if voters * 3 > self.authority_list.len().saturating_sub(1) {
+                self.goto_round(round);
+            }
+        }
+        self.try_commit();
+    }
+
+    fn goto_round(&mut self, round: u64) {
+        self.round = round;
+        self.step = MockStep::Propose;
+        self.prevote();
+    }
+
+    fn propose(&mut self) {
+        if self.step != MockStep::Propose || self.proposals.contains_key(&self.round) {
+            return;
+        }
+        let (content, lock_round, lock_votes) = match self.lock {
+            Some((round, ref proposal, ref votes)) => {
+                let round = if self.fault == Some(Fault::WrongLockRound) {
+                    round + 1
+                } else {
+                    round
+                };
+                (proposal.clone(), Some(round), votes.clone())
+            }
+            None => match self.feed {
+                Some(ref feed) => (feed.clone(), None, Vec::new()),
+                None => return,
+            },
+        };
+        let proposal = Proposal {
+            height: self.height,
+            round: self.round,
+            content,
+            proposer: self.address.clone(),
+            lock_round,
+            lock_votes,
+        };
+        self.outbox.push_back(FrameRecv::Proposal(proposal.clone()));
+        self.proposals.insert(self.round, proposal);
+        self.prevote();
+    }
+
+    fn prevote(&mut self) {
+        if self.step != MockStep::Propose {
+            return;
+        }
+        let proposal = match self.proposals.get(&self.round) {
+            Some(p) => p.clone(),
+            None => return,
+        };
+        let lock = self
+            .lock
+            .as_ref()
+            .map(|(round, proposal, _)| (*round, proposal.as_slice()));
+        let polc_round = proposal
+            .lock_round
+            .filter(|_| is_polc(&proposal, self.height, &self.authority_list));
+        let vote = if can_prevote(lock, &proposal.content, polc_round, self.round) {
+            proposal.content.clone()
+        } else {
+            Vec::new()
+        };
+        self.step = MockStep::Prevote;
+        match self.fault {
+            Some(Fault::PrevoteNil) => self.send_vote(VoteType::Prevote, Vec::new()),
+            Some(Fault::Equivocate) => {
+                let other = if vote.is_empty() {
+                    proposal.content
+                } else {
+                    Vec::new()
+                };
+                self.send_vote(VoteType::Prevote, vote);
+                self.send_vote(VoteType::Prevote, other);
+            }
+            Some(Fault::PrecommitWithoutQuorum) => {
+                self.send_vote(VoteType::Prevote, vote.clone());
+                self.step = MockStep::Precommit;
+                self.send_vote(VoteType::Precommit, vote);
+                self.try_commit();
+            }
+            _ => self.send_vote(VoteType::Prevote, vote),
+        }
+    }
+
+    fn timeout(&mut self) {
+        if self.step != MockStep::Prevote {
+            return;
+        }
+        let (count, polka) = match self.votes.get(&(self.round, VoteType::Prevote)) {
+            Some(votes) => {
+                let mut counter: HashMap<&Vec<u8>, usize> = HashMap::new();
+                for proposal in votes.values() {
+                    *counter.entry(proposal).or_default() += 1;
+                }
+                let polka = counter
+                    .into_iter()
+                    .find(|(_, count)| self.is_above_threshold(*count))
+                    .map(|(p, _)| p.clone());
+                (votes.len(), polka)
+            }
+            None => (0, None),
+        };
+        if !self.is_above_threshold(count) {
+            return;
+        }
+
+        let vote = match LockChange::by_polka(polka) {
+            LockChange::Unlock => {
+                self.lock = None;
+                Vec::new()
+            }
+            LockChange::Lock(p) => {
+                let votes = self.votes[&(self.round, VoteType::Prevote)]
+                    .iter()
+                    .filter(|(_, v)| **v == p)
+                    .map(|(voter, _)| Vote {
+                        height: self.height,
+                        round: self.round,
+                        vote_type: VoteType::Prevote,
+                        proposal: p.clone(),
+                        voter: voter.clone(),
+                    })
+                    .collect();
+                if self.fault != Some(Fault::IgnoreLock) {
+                    self.lock = Some((self.round, p.clone(), votes));
+                }
+                p
+            }
+            LockChange::Keep => Vec::new(),
+        };
+        self.step = MockStep::Precommit;
+        self.send_vote(VoteType::Precommit, vote);
+        self.try_commit();
+    }
+
+    fn send_vote(&mut self, vote_type: VoteType, proposal: Vec<u8>) {
+        let vote = Vote {
+            height: self.height,
+            round: self.round,
+            vote_type,
+            proposal,
+            voter: self.address.clone(),
+        };
+        self.outbox.push_back(FrameRecv::Vote(vote.clone()));
+        self.votes
+            .entry((vote.round, vote.vote_type))
+            .or_default()
+            .insert(vote.voter, vote.proposal);
+    }
+
+    fn try_commit(&mut self) {
+        if self.committed {
+            return;
+        }
+        let mut result = None;
+        for ((_, vote_type), votes) in self.votes.iter() {
+            if *vote_type != VoteType::Precommit {
+                continue;
+            }
+            let mut counter: HashMap<&Vec<u8>, usize> = HashMap::new();
+            for proposal in votes.values().filter(|p| !p.is_empty()) {
+                *counter.entry(proposal).or_default() += 1;
+            }
+            let without_quorum = self.fault == Some(Fault::CommitWithoutQuorum);
+            result = counter
+                .into_iter()
+                .find(|(_, count)| without_quorum || self.is_above_threshold(*count))
+                .map(|(p, _)| p.clone());
+            if result.is_some() {
+                break;
+            }
+        }
+        if let Some(result) = result {
+            self.committed = true;
+            let node = self
+                .authority_list
+                .iter()
+                .position(|a| a == &self.address)
+                .unwrap_or_default() as u8;
+            let commit = Commit {
+                node,
+                height: self.height,
+                result,
+            };
+            if self.fault == Some(Fault::DoubleCommit) {
+                self.commits.push_back(commit.clone());
+            }
+            self.commits.push_back(commit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::BftError;
+    use crate::whitebox::{
+        actuator::Actuator,
+        collection::{
+            safety::{check_trace, Violation},
+            storage::TempDb,
+            trace::TraceReader,
+        },
+        correctness::{
+            model_check::ModelCheckConfig,
+            property::PropertyConfig,
+            scenario::{Behavior, RoundPlan, Scenario},
+            suite::{FaultType, Suite, SuiteConfig, SuiteRegistry, SuiteSelector},
+            test_case::*,
+        },
+    };
+
+    fn generate_authority(num: u8) -> Vec<Address> {
+        (0..num).map(|i| vec![i]).collect()
+    }
+
+    fn actuator(authority_num: u8, db: &TempDb) -> Actuator<MockNode> {
+        let mut actuator = Actuator::new(
+            MockNode::new(vec![0]),
+            0,
+            0,
+            generate_authority(authority_num),
+            db.path(),
+        );
+        actuator.set_no_commit_window(Duration::from_millis(1));
+        actuator
+    }
+
+    // A scenario that a correct node passes and the faulty node fails.
+    fn fault_case(fault: Fault) -> BftTest {
+        let mut builder = Scenario::builder(4);
+        match fault {
+            // +2/3 nil prevotes, while the node has precommitted the proposal
+            Fault::PrecommitWithoutQuorum => {
+                builder
+                    .plan(RoundPlan::all(4, Behavior::Nil, Behavior::Nil))
+                    .expect_no_commit();
+            }
+            // the node is locked in the round 0 and proposes in the round 3
+            Fault::IgnoreLock | Fault::WrongLockRound => {
+                builder
+                    .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Nil))
+                    .expect_no_commit()
+                    .skip_round()
+                    .skip_round()
+                    .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+                    .expect_commit();
+            }
+            Fault::DoubleCommit => {
+                builder
+                    .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+                    .expect_commit()
+                    .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+                    .expect_commit();
+            }
+            // a prevote for the proposal is expected
+            Fault::Equivocate | Fault::PrevoteNil => {
+                builder
+                    .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+                    .expect_commit();
+            }
+            // 2 precommits for the proposal with its own
+            Fault::CommitWithoutQuorum => {
+                builder
+                    .plan(RoundPlan::new(
+                        vec![Behavior::Normal; 3],
+                        vec![Behavior::Normal, Behavior::Nil, Behavior::Nil],
+                    ))
+                    .expect_no_commit();
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_mock_all_test() {
+        let db = TempDb::new("mock_all_4");
+        actuator(4, &db).all_test().unwrap();
+    }
+
+    #[test]
+    fn test_mock_safety() {
+        let db = TempDb::new("mock_safety");
+        let db_path = db.path();
+        actuator(4, &db).all_test().unwrap();
+        for report in check_trace(db_path).unwrap().into_iter() {
+            assert!(report.is_safe(), "{:?}", report);
+        }
+    }
+
+    #[test]
+    fn test_nil_polka_unlock() {
+        // lock in round 0, unlock by the nil prevotes in round 1, and commit a new
+        // proposal in round 2
+        let db = TempDb::new("mock_unlock");
+        let db_path = db.path();
+        let cases = Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Offline))
+            .expect_no_commit()
+            .plan(RoundPlan::all(4, Behavior::Nil, Behavior::Offline))
+            .expect_no_commit()
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .unwrap();
+        actuator(4, &db).proc_test(cases).unwrap();
+        let reports = check_trace(db_path).unwrap();
+        assert!(reports[0].is_safe(), "{:?}", reports[0]);
+    }
+
+    #[test]
+    fn test_faulty_nodes_safety() {
+        let config = SuiteConfig {
+            continue_on_failure: true,
+            ..SuiteConfig::default()
+        };
+        for fault in vec![Fault::Equivocate, Fault::CommitWithoutQuorum].into_iter() {
+            let db = TempDb::new(&format!("mock_safety_{:?}", fault));
+            let db_path = db.path();
+            let mut actuator = Actuator::new(
+                MockNode::faulty(vec![0], fault),
+                0,
+                0,
+                generate_authority(4),
+                db_path,
+            );
+            actuator.set_no_commit_window(Duration::from_millis(1));
+            actuator.set_seed(0);
+            actuator.run_suites(&config);
+
+            let violation = check_trace(db_path)
+                .unwrap()
+                .into_iter()
+                .flat_map(|report| report.violations)
+                .next();
+            match (fault, violation) {
+                (Fault::Equivocate, Some(Violation::DoubleVote { .. }))
+                | (Fault::CommitWithoutQuorum, Some(Violation::UnbackedCommit { .. })) => (),
+                (fault, v) => panic!("Fault {:?} is not found in trace, get {:?}", fault, v),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mock_all_test_seven() {
+        let db = TempDb::new("mock_all_7");
+        actuator(7, &db).all_test().unwrap();
+    }
+
+    #[test]
+    fn test_mock_run_suites() {
+        let config = SuiteConfig {
+            include: vec![SuiteSelector::Tag("byzantine".to_string())],
+            exclude: vec![SuiteSelector::Name("two_*".to_string())],
+            continue_on_failure: false,
+        };
+        let db = TempDb::new("mock_suites");
+        let summary = actuator(4, &db).run_suites(&config);
+        assert_eq!(summary.passed(), vec!["one_byzantine"]);
+        assert_eq!(summary.skipped().len(), summary.results.len() - 1);
+
+        // the rest suites are skipped after the first failure
+        let db = TempDb::new("mock_suites_fault");
+        let db_path = db.path();
+        let mut actuator = Actuator::new(
+            MockNode::faulty(vec![0], Fault::PrevoteNil),
+            0,
+            0,
+            generate_authority(4),
+            db_path,
+        );
+        actuator.set_no_commit_window(Duration::from_millis(1));
+        let summary = actuator.run_suites(&SuiteConfig::default());
+        assert_eq!(summary.failed(), vec!["no_byzantine"]);
+        assert_eq!(summary.skipped().len(), summary.results.len() - 1);
+
+        let config = SuiteConfig {
+            continue_on_failure: true,
+            ..SuiteConfig::default()
+        };
+        let summary = actuator.run_suites(&config);
+        assert!(!summary.is_success() && summary.skipped().is_empty());
+        assert!(summary.into_result().is_err());
+    }
+
+    #[test]
+    fn test_mock_custom_suite() {
+        let mut registry = SuiteRegistry::default();
+        registry
+            .register(
+                Suite::new("lock_leap", |authority_num, _| {
+                    Scenario::builder(authority_num)
+                        .plan(RoundPlan::all(
+                            authority_num,
+                            Behavior::Normal,
+                            Behavior::Nil,
+                        ))
+                        .expect_no_commit()
+                        .skip_round()
+                        .plan(RoundPlan::all(
+                            authority_num,
+                            Behavior::Normal,
+                            Behavior::Normal,
+                        ))
+                        .expect_commit()
+                        .build()
+                        .unwrap()
+                })
+                .with_fault(FaultType::Other("lock leap".to_string())),
+            )
+            .unwrap();
+        let db = TempDb::new("mock_custom_suite");
+        let mut actuator = actuator(4, &db);
+        actuator.set_suite_registry(registry);
+        let config = SuiteConfig {
+            include: vec![SuiteSelector::Name("*leap".to_string())],
+            ..SuiteConfig::default()
+        };
+        let summary = actuator.run_suites(&config);
+        assert_eq!(summary.passed(), vec!["round_leap", "lock_leap"]);
+    }
+
+    #[test]
+    fn test_round_leap() {
+        // the testing node is the proposer of round 3 and 7 of height 1, and it should
+        // jump to round 7 from round 0 by the prevotes
+        let db = TempDb::new("mock_round_leap");
+        let mut actuator = actuator(4, &db);
+        let mut builder = Scenario::builder(4);
+        builder
+            .plan(RoundPlan::all(4, Behavior::Byzantine, Behavior::Nil))
+            .expect_no_commit();
+        for _ in 0..6 {
+            builder.skip_round();
+        }
+        let cases = builder
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .unwrap();
+        actuator.proc_test(cases).unwrap();
+    }
+
+    #[test]
+    fn test_round_leap_by_peer() {
+        // the testing node jumps to round 2 by the prevotes before the proposal of the
+        // simulated proposer
+        let db = TempDb::new("mock_round_leap_peer");
+        let db_path = db.path();
+        let mut actuator = actuator(4, &db);
+        let cases = Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Byzantine, Behavior::Nil))
+            .expect_no_commit()
+            .skip_round()
+            .plan(RoundPlan::all(4, Behavior::Normal, Behavior::Normal))
+            .expect_commit()
+            .build()
+            .unwrap();
+        actuator.proc_test(cases).unwrap();
+        let reader = TraceReader::open(db_path).unwrap();
+        let proposal = reader.proposals_by_proposer(1, &[3]).unwrap();
+        let prevotes = reader.votes_at(1, 1, 2, VoteType::Prevote).unwrap();
+        assert_eq!(proposal[0].msg.round, 2);
+        assert!(prevotes[0].seq < proposal[0].seq);
+    }
+
+    #[test]
+    fn test_leap_by_votes() {
+        let node = MockNode::new(vec![0]);
+        node.send(FrameSend::Status(Status {
+            height: 0,
+            authority_list: generate_authority(4),
+        }));
+        // a proposal of a later round does not move the node
+        node.send(FrameSend::Proposal(Proposal {
+            height: 1,
+            round: 2,
+            content: vec![1],
+            proposer: vec![3],
+            lock_round: None,
+            lock_votes: Vec::new(),
+        }));
+        assert_eq!(node.recv_timeout(Duration::from_secs(0)), None);
+
+        // while f + 1 votes of the round do
+        for voter in 1..3 {
+            node.send(FrameSend::Vote(Vote {
+                height: 1,
+                round: 2,
+                vote_type: VoteType::Prevote,
+                proposal: vec![1],
+                voter: vec![voter],
+            }));
+        }
+        match node.recv_timeout(Duration::from_secs(0)) {
+            Some(FrameRecv::Vote(v)) => assert_eq!((v.round, v.proposal), (2, vec![1])),
+            msg => panic!("Get {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn test_mock_property() {
+        let db = TempDb::new("mock_property");
+        let mut actuator = actuator(4, &db);
+        actuator.set_seed(0);
+        let res = actuator.property_test(&PropertyConfig::default()).unwrap();
+        assert!(res.is_ok(), "{:?}", res);
+    }
+
+    #[test]
+    fn test_mock_model_check() {
+        let db = TempDb::new("mock_model_check");
+        let db_path = db.path();
+        let report = actuator(4, &db)
+            .model_check(&ModelCheckConfig::default())
+            .unwrap();
+        assert!(report.violation.is_none(), "{:?}", report.violation);
+        assert_eq!(report.total, report.round_plans as u64);
+        assert_eq!(report.passed, report.total);
+        assert!(report.commit_rounds > 0 && report.no_commit_rounds > 0);
+
+        // every sequence is a run, and the votes of the testing node are recorded
+        let conn = rusqlite::Connection::open(db_path).unwrap();
+        let count = |sql: &str| -> i64 {
+            conn.query_row(sql, rusqlite::NO_PARAMS, |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(
+            count("SELECT COUNT(*) FROM runs WHERE outcome = 'passed'"),
+            report.total as i64
+        );
+        assert_eq!(
+            count(
+                "SELECT COUNT(*) FROM vote WHERE direction = 'received'
+                    AND origin = 'testing_node' AND run_id = 1"
+            ),
+            2
+        );
+    }
+
+    #[test]
+    fn test_silent_node() {
+        // a correct node waits for +2/3 prevotes before precommit
+        let db = TempDb::new("mock_silent");
+        let mut actuator = actuator(4, &db);
+        actuator.set_recv_timeout(Duration::from_millis(10));
+        let cases = Scenario::builder(4)
+            .plan(RoundPlan::all(4, Behavior::Offline, Behavior::Normal))
+            .expect_no_commit()
+            .build()
+            .unwrap();
+        match actuator.proc_test(cases) {
+            Err(BftError::GetNoVote(1, 0, VoteType::Precommit)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_faulty_nodes() {
+        for fault in Fault::all().into_iter() {
+            let cases = fault_case(fault);
+            // a correct node passes the scenario
+            let db = TempDb::new(&format!("mock_fault_{:?}_correct", fault));
+            actuator(4, &db).proc_test(cases.clone()).unwrap();
+
+            for seed in 0..3 {
+                let db = TempDb::new(&format!("mock_fault_{:?}_{}", fault, seed));
+                let mut actuator = Actuator::new(
+                    MockNode::faulty(vec![0], fault),
+                    0,
+                    0,
+                    generate_authority(4),
+                    db.path(),
+                );
+                actuator.set_no_commit_window(Duration::from_millis(1));
+                actuator.set_seed(seed);
+                // the nil prevote of the testing node in the round 0
+                let nil_prevote = |v: &Vote| {
+                    (v.height, v.round, v.vote_type.clone(), v.voter.clone())
+                        == (1, 0, VoteType::Prevote, vec![0])
+                        && v.proposal.is_empty()
+                };
+                match (fault, actuator.proc_test(cases.clone())) {
+                    (Fault::PrecommitWithoutQuorum, Err(BftError::ShouldNotPrecommit(1, 0)))
+                    | (Fault::IgnoreLock, Err(BftError::IllegalProposal(1, 3)))
+                    | (Fault::DoubleCommit, Err(BftError::MultipleCommit(1)))
+                    | (Fault::WrongLockRound, Err(BftError::IllegalProposal(1, 3)))
+                    | (Fault::CommitWithoutQuorum, Err(BftError::CommitInvalid(1))) => (),
+                    (Fault::Equivocate, Err(BftError::IllegalVote(ref v)))
+                    | (Fault::PrevoteNil, Err(BftError::IllegalVote(ref v)))
+                        if nil_prevote(v) => {}
+                    (fault, res) => panic!(
+                        "Fault {:?} is not caught with seed {:?}, get {:?}",
+                        fault, seed, res
+                    ),
+                }
+            }
+        }
+    }
+}
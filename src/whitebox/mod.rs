@@ -1,8 +1,17 @@
 ///
 pub mod actuator;
 ///
+pub mod async_support;
+///
 pub mod collection;
 ///
 pub mod correctness;
 ///
+#[cfg(feature = "mock")]
+pub mod mock;
+///
+pub mod reference;
+///
+pub mod transport;
+///
 pub mod types;
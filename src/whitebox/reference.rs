@@ -0,0 +1,426 @@
+use crate::whitebox::{
+    collection::{proposal_cache::ProposalCache, vote_cache::VoteCache},
+    types::{Address, Hash, Proposal, Vote, VoteType},
+};
+
+/// The lock of a node. A node locks a proposal when it sees a PoLC, which is +2/3
+/// prevotes for the proposal in a round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lock {
+    /// The round of the PoLC.
+    pub round: u64,
+    /// The locked proposal.
+    pub proposal: Hash,
+    /// The prevotes of the PoLC.
+    pub votes: Vec<Vote>,
+}
+
+/// The change of a lock by the prevotes of a round. The lock rules are shared by the
+/// reference state machine, the mock node and the safety checker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockChange {
+    /// Lock the proposal with +2/3 prevotes in the round.
+    Lock(Hash),
+    /// Unlock with +2/3 nil prevotes in the round.
+    Unlock,
+    /// Keep the lock without +2/3 prevotes for anything.
+    Keep,
+}
+
+impl LockChange {
+    /// A function to get the change of a lock by the `polka` of a round, which is the
+    /// proposal with +2/3 prevotes in the round, or an empty hash for nil.
+    pub fn by_polka(polka: Option<Hash>) -> Self {
+        match polka {
+            Some(ref proposal) if proposal.is_empty() => LockChange::Unlock,
+            Some(proposal) => LockChange::Lock(proposal),
+            None => LockChange::Keep,
+        }
+    }
+}
+
+/// A function to check whether a node with the `lock` of a round and a proposal can
+/// prevote the `proposal` in the `round`. A locked node prevotes another proposal only
+/// with a PoLC of it, whose `polc_round` is later than the lock round and earlier than
+/// the `round`.
+pub fn can_prevote(
+    lock: Option<(u64, &[u8])>,
+    proposal: &[u8],
+    polc_round: Option<u64>,
+    round: u64,
+) -> bool {
+    match lock {
+        Some((lock_round, lock_proposal)) if lock_proposal != proposal => polc_round
+            .map(|polc_round| polc_round > lock_round && polc_round < round)
+            .unwrap_or(false),
+        _ => true,
+    }
+}
+
+/// A function to check whether the lock votes of a proposal at the `height` are +2/3
+/// prevotes for it in the lock round, by the voters in the `authority_list`.
+pub(crate) fn is_polc(proposal: &Proposal, height: u64, authority_list: &[Address]) -> bool {
+    let round = match proposal.lock_round {
+        Some(round) => round,
+        None => return false,
+    };
+    let mut voters = proposal
+        .lock_votes
+        .iter()
+        .filter(|v| {
+            v.height == height
+                && v.round == round
+                && v.vote_type == VoteType::Prevote
+                && v.proposal == proposal.content
+                && authority_list.contains(&v.voter)
+        })
+        .map(|v| &v.voter)
+        .collect::<Vec<_>>();
+    voters.sort();
+    voters.dedup();
+    voters.len() * 3 > authority_list.len() * 2
+}
+
+/// A reference Tendermint-style state machine. It is fed with the proposals and the votes
+/// a node receives and sends, and computes exactly which proposal, prevote, precommit and
+/// commit a correct node should emit by the lock, the PoLC and the valid round. An empty
+/// proposal hash means nil.
+#[derive(Clone, Debug)]
+pub struct StateMachine {
+    height: u64,
+    round: u64,
+    authority_list: Vec<Address>,
+    lock: Option<Lock>,
+    valid: Option<Lock>,
+    votes: VoteCache,
+    proposals: ProposalCache,
+}
+
+impl StateMachine {
+    /// A function to create a new state machine at the round 0 of the `height`.
+    pub fn new(height: u64, authority_list: Vec<Address>) -> Self {
+        StateMachine {
+            height,
+            round: 0,
+            authority_list,
+            lock: None,
+            valid: None,
+            votes: VoteCache::new(),
+            proposals: ProposalCache::new(),
+        }
+    }
+
+    /// A function to get the current height.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// A function to get the current round.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// A function to get the lock.
+    pub fn lock(&self) -> Option<&Lock> {
+        self.lock.as_ref()
+    }
+
+    /// A function to get the valid round, which is the latest PoLC of the height. It is
+    /// kept after the node unlocks with +2/3 nil prevotes.
+    pub fn valid(&self) -> Option<&Lock> {
+        self.valid.as_ref()
+    }
+
+    /// A function to get the proposal of the current round.
+    pub fn proposal(&mut self) -> Option<Proposal> {
+        self.proposals.get_proposal(self.height, self.round)
+    }
+
+    /// A function to set a new authority list, which takes effect immediately.
+    pub fn set_authority_list(&mut self, authority_list: Vec<Address>) {
+        self.authority_list = authority_list;
+    }
+
+    /// A function to go to the round 0 of a new height. The lock and the valid round
+    /// are cleaned.
+    pub fn goto_height(&mut self, height: u64) {
+        self.height = height;
+        self.round = 0;
+        self.lock = None;
+        self.valid = None;
+        self.votes.clear_prevote_count();
+    }
+
+    /// A function to go to a new round of the current height.
+    pub fn goto_round(&mut self, round: u64) {
+        self.round = round;
+    }
+
+    /// A function to add a proposal. Return `false` if there is a proposal of the same
+    /// height and round.
+    pub fn add_proposal(&mut self, proposal: Proposal) -> bool {
+        self.proposals.add(proposal)
+    }
+
+    /// A function to add a vote. Return `false` if the voter has voted in the same step.
+    pub fn add_vote(&mut self, vote: Vote) -> bool {
+        self.votes.add(vote)
+    }
+
+    /// A function to compute the proposal a correct proposer should emit in the current
+    /// round with the `feed`. A locked proposer proposes the locked proposal with the
+    /// lock round and the PoLC, otherwise it proposes the feed.
+    pub fn expect_proposal(&self, proposer: Address, feed: Hash) -> Proposal {
+        let (content, lock_round, lock_votes) = match self.lock {
+            Some(ref lock) => (lock.proposal.clone(), Some(lock.round), lock.votes.clone()),
+            None => (feed, None, Vec::new()),
+        };
+        Proposal {
+            height: self.height,
+            round: self.round,
+            content,
+            proposer,
+            lock_round,
+            lock_votes,
+        }
+    }
+
+    /// A function to check whether a proposal of the current round with the `feed` is
+    /// what a correct proposer may emit. Besides the expected proposal, a proposer
+    /// unlocked by +2/3 nil prevotes may propose the proposal of the valid round again
+    /// with the valid round as the lock round.
+    pub fn is_expected_proposal(&self, proposal: &Proposal, feed: Hash) -> bool {
+        let expect = self.expect_proposal(proposal.proposer.clone(), feed);
+        let repropose = match (&self.lock, &self.valid) {
+            (None, Some(valid)) => {
+                proposal.content == valid.proposal && proposal.lock_round == Some(valid.round)
+            }
+            _ => false,
+        };
+        (proposal.content == expect.content && proposal.lock_round == expect.lock_round)
+            || repropose
+    }
+
+    /// A function to compute the prevote a correct node should emit in the current
+    /// round. It prevotes nil without a proposal. A locked node prevotes the proposal
+    /// only if it is the locked one, or it carries a valid PoLC later than the lock.
+    pub fn expect_prevote(&mut self) -> Hash {
+        let proposal = match self.proposals.get_proposal(self.height, self.round) {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
+        let lock = self
+            .lock
+            .as_ref()
+            .map(|lock| (lock.round, lock.proposal.as_slice()));
+        let polc_round = proposal
+            .lock_round
+            .filter(|_| is_polc(&proposal, self.height, &self.authority_list));
+        if can_prevote(lock, &proposal.content, polc_round, self.round) {
+            proposal.content
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// A function to get the proposal with +2/3 prevotes in the current round. Return
+    /// an empty hash if nil gets +2/3 prevotes, and `None` if nothing gets it.
+    pub fn polka(&mut self) -> Option<Hash> {
+        let prevotes = self
+            .votes
+            .get_voteset(self.height, self.round, VoteType::Prevote)?;
+        prevotes
+            .votes_by_proposal
+            .iter()
+            .find(|(_, count)| self.is_above_threshold(**count))
+            .map(|(proposal, _)| proposal.clone())
+    }
+
+    /// A function to update the lock by the prevotes of the current round. The node
+    /// locks the proposal with a PoLC and unlocks with +2/3 nil prevotes. The PoLC is
+    /// the valid round as well, which is not cleaned by the unlock.
+    pub fn handle_prevotes(&mut self) {
+        let polka = self.polka();
+        match LockChange::by_polka(polka) {
+            LockChange::Unlock => self.lock = None,
+            LockChange::Lock(proposal) => {
+                let votes = self
+                    .votes
+                    .get_voteset(self.height, self.round, VoteType::Prevote)
+                    .map(|set| {
+                        set.extract_polc(self.height, self.round, VoteType::Prevote, &proposal)
+                    })
+                    .unwrap_or_default();
+                let lock = Lock {
+                    round: self.round,
+                    proposal,
+                    votes,
+                };
+                self.valid = Some(lock.clone());
+                self.lock = Some(lock);
+            }
+            LockChange::Keep => (),
+        }
+    }
+
+    /// A function to compute the precommit a correct node should emit in the current
+    /// round. Return `None` if the node should not precommit before +2/3 prevotes. It
+    /// precommits the proposal with a PoLC in the round, otherwise it precommits nil.
+    pub fn expect_precommit(&mut self) -> Option<Hash> {
+        let count = self
+            .votes
+            .get_voteset(self.height, self.round, VoteType::Prevote)
+            .map(|set| set.count)
+            .unwrap_or(0);
+        if !self.is_above_threshold(count) {
+            return None;
+        }
+        Some(self.polka().unwrap_or_default())
+    }
+
+    /// A function to compute the commit a correct node should emit in the current round.
+    /// It commits the proposal with +2/3 precommits.
+    pub fn expect_commit(&mut self) -> Option<Hash> {
+        let precommits = self
+            .votes
+            .get_voteset(self.height, self.round, VoteType::Precommit)?;
+        precommits
+            .votes_by_proposal
+            .iter()
+            .find(|(proposal, count)| !proposal.is_empty() && self.is_above_threshold(**count))
+            .map(|(proposal, _)| proposal.clone())
+    }
+
+    fn is_above_threshold(&self, num: usize) -> bool {
+        num * 3 > self.authority_list.len() * 2
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vote(vote_type: VoteType, round: u64, voter: u8, proposal: &[u8]) -> Vote {
+        Vote {
+            height: 1,
+            round,
+            vote_type,
+            proposal: proposal.to_vec(),
+            voter: vec![voter],
+        }
+    }
+
+    fn proposal(round: u64, content: &[u8], lock: Option<&Lock>) -> Proposal {
+        Proposal {
+            height: 1,
+            round,
+            content: content.to_vec(),
+            proposer: vec![1],
+            lock_round: lock.map(|l| l.round),
+            lock_votes: lock.map(|l| l.votes.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn authority_list() -> Vec<Address> {
+        (0..4).map(|i| vec![i]).collect()
+    }
+
+    #[test]
+    fn test_lock_and_commit() {
+        let mut sm = StateMachine::new(1, authority_list());
+        assert_eq!(sm.expect_prevote(), Vec::<u8>::new());
+        assert_eq!(sm.expect_proposal(vec![0], vec![1]).content, vec![1]);
+
+        sm.add_proposal(proposal(0, &[1], None));
+        assert_eq!(sm.expect_prevote(), vec![1]);
+        sm.add_vote(vote(VoteType::Prevote, 0, 0, &[1]));
+        sm.add_vote(vote(VoteType::Prevote, 0, 1, &[1]));
+        assert_eq!(sm.expect_precommit(), None);
+        sm.add_vote(vote(VoteType::Prevote, 0, 2, &[1]));
+        assert_eq!(sm.expect_precommit(), Some(vec![1]));
+        sm.handle_prevotes();
+        let lock = sm.lock().cloned().unwrap();
+        assert_eq!((lock.round, lock.votes.len()), (0, 3));
+
+        for voter in 0..2 {
+            sm.add_vote(vote(VoteType::Precommit, 0, voter, &[1]));
+        }
+        sm.add_vote(vote(VoteType::Precommit, 0, 2, &[]));
+        assert_eq!(sm.expect_commit(), None);
+
+        // the locked node proposes the locked proposal, and prevotes nil for others
+        sm.goto_round(1);
+        let expect = sm.expect_proposal(vec![0], vec![2]);
+        assert_eq!((expect.content, expect.lock_round), (vec![1], Some(0)));
+        sm.add_proposal(proposal(1, &[2], None));
+        assert_eq!(sm.expect_prevote(), Vec::<u8>::new());
+
+        sm.goto_round(2);
+        sm.add_proposal(proposal(2, &[1], Some(&lock)));
+        assert_eq!(sm.expect_prevote(), vec![1]);
+        for voter in 0..3 {
+            sm.add_vote(vote(VoteType::Prevote, 2, voter, &[1]));
+            sm.add_vote(vote(VoteType::Precommit, 2, voter, &[1]));
+        }
+        assert_eq!(sm.expect_commit(), Some(vec![1]));
+
+        assert_eq!(sm.valid().map(|v| v.round), Some(0));
+        sm.goto_height(2);
+        assert!(sm.lock().is_none() && sm.valid().is_none());
+    }
+
+    #[test]
+    fn test_unlock() {
+        let mut sm = StateMachine::new(1, authority_list());
+        sm.add_proposal(proposal(0, &[1], None));
+        for voter in 0..3 {
+            sm.add_vote(vote(VoteType::Prevote, 0, voter, &[1]));
+        }
+        sm.handle_prevotes();
+        assert!(sm.lock().is_some());
+
+        // a later PoLC for another proposal unlocks the node
+        let mut polc = Lock {
+            round: 1,
+            proposal: vec![2],
+            votes: (1..4)
+                .map(|i| vote(VoteType::Prevote, 1, i, &[2]))
+                .collect(),
+        };
+        sm.goto_round(2);
+        sm.add_proposal(proposal(2, &[2], Some(&polc)));
+        assert_eq!(sm.expect_prevote(), vec![2]);
+
+        // a forged PoLC does not, nor do the prevotes of another round
+        let stale = Lock {
+            votes: (1..4)
+                .map(|i| vote(VoteType::Prevote, 0, i, &[2]))
+                .collect(),
+            ..polc.clone()
+        };
+        sm.goto_round(3);
+        sm.add_proposal(proposal(3, &[2], Some(&stale)));
+        assert_eq!(sm.expect_prevote(), Vec::<u8>::new());
+        polc.votes.truncate(2);
+        sm.goto_round(4);
+        sm.add_proposal(proposal(4, &[2], Some(&polc)));
+        assert_eq!(sm.expect_prevote(), Vec::<u8>::new());
+
+        // +2/3 nil prevotes unlock the node
+        for voter in 0..3 {
+            sm.add_vote(vote(VoteType::Prevote, 4, voter, &[]));
+        }
+        assert_eq!(sm.expect_precommit(), Some(Vec::new()));
+        sm.handle_prevotes();
+        assert!(sm.lock().is_none());
+
+        // the valid round is kept, and the unlocked proposer may propose it again
+        let valid = sm.valid().cloned().unwrap();
+        assert_eq!((valid.round, valid.proposal.clone()), (0, vec![1]));
+        sm.goto_round(5);
+        let repropose = proposal(5, &[1], Some(&valid));
+        assert!(sm.is_expected_proposal(&repropose, vec![3]));
+        assert!(sm.is_expected_proposal(&proposal(5, &[3], None), vec![3]));
+        assert!(!sm.is_expected_proposal(&proposal(5, &[2], None), vec![3]));
+    }
+}
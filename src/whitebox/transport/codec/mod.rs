@@ -0,0 +1,221 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    transport::{NodeInput, NodeOutput},
+    types::FrameResult,
+};
+
+#[cfg(feature = "bincode")]
+use crate::whitebox::types::{Commit, Feed, Proposal, Status, Vote};
+#[cfg(feature = "bincode")]
+use serde_derive::{Deserialize, Serialize};
+
+///
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+///
+#[cfg(feature = "rlp")]
+pub mod rlp;
+
+#[cfg(feature = "protobuf")]
+pub use self::protobuf::ProtobufCodec;
+#[cfg(feature = "rlp")]
+pub use self::rlp::RlpCodec;
+
+/// A codec of the messages between the framework and an out-of-process testing node.
+/// A transport-based `Support` encodes the `NodeInput`s it sends and decodes the
+/// `NodeOutput`s it receives by the codec, and a testing node does the opposite.
+pub trait Codec: Send + Sync + 'static {
+    /// The name of the codec.
+    fn name(&self) -> &'static str;
+    /// Encode a message to the testing node.
+    fn encode_input(&self, input: &NodeInput) -> FrameResult<Vec<u8>>;
+    /// Decode a message to the testing node.
+    fn decode_input(&self, bytes: &[u8]) -> FrameResult<NodeInput>;
+    /// Encode a message from the testing node.
+    fn encode_output(&self, output: &NodeOutput) -> FrameResult<Vec<u8>>;
+    /// Decode a message from the testing node.
+    fn decode_output(&self, bytes: &[u8]) -> FrameResult<NodeOutput>;
+}
+
+/// The serde JSON codec, which is always available. A message is encoded as
+/// `{"type":"vote","data":{..}}`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode_input(&self, input: &NodeInput) -> FrameResult<Vec<u8>> {
+        serde_json::to_vec(input).map_err(codec_err)
+    }
+
+    fn decode_input(&self, bytes: &[u8]) -> FrameResult<NodeInput> {
+        serde_json::from_slice(bytes).map_err(codec_err)
+    }
+
+    fn encode_output(&self, output: &NodeOutput) -> FrameResult<Vec<u8>> {
+        serde_json::to_vec(output).map_err(codec_err)
+    }
+
+    fn decode_output(&self, bytes: &[u8]) -> FrameResult<NodeOutput> {
+        serde_json::from_slice(bytes).map_err(codec_err)
+    }
+}
+
+/// The bincode codec, which is enabled by the `bincode` feature. A message is encoded as
+/// a little-endian `u32` variant index followed by the fields, whose variant indexes are
+/// the declaration order of `NodeInput` and `NodeOutput`.
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+// Bincode does not support the adjacently tagged enums, so the messages are encoded by
+// the externally tagged ones.
+#[cfg(feature = "bincode")]
+#[derive(Serialize, Deserialize)]
+enum BincodeInput {
+    Proposal(Proposal),
+    Vote(Vote),
+    Feed(Feed),
+    Status(Status),
+    Reset,
+    Stop,
+}
+
+#[cfg(feature = "bincode")]
+#[derive(Serialize, Deserialize)]
+enum BincodeOutput {
+    Proposal(Proposal),
+    Vote(Vote),
+    Commit(Commit),
+}
+
+#[cfg(feature = "bincode")]
+impl Codec for BincodeCodec {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn encode_input(&self, input: &NodeInput) -> FrameResult<Vec<u8>> {
+        let input = match input.clone() {
+            NodeInput::Proposal(p) => BincodeInput::Proposal(p),
+            NodeInput::Vote(v) => BincodeInput::Vote(v),
+            NodeInput::Feed(f) => BincodeInput::Feed(f),
+            NodeInput::Status(s) => BincodeInput::Status(s),
+            NodeInput::Reset => BincodeInput::Reset,
+            NodeInput::Stop => BincodeInput::Stop,
+        };
+        bincode::serialize(&input).map_err(codec_err)
+    }
+
+    fn decode_input(&self, bytes: &[u8]) -> FrameResult<NodeInput> {
+        let input = match bincode::deserialize(bytes).map_err(codec_err)? {
+            BincodeInput::Proposal(p) => NodeInput::Proposal(p),
+            BincodeInput::Vote(v) => NodeInput::Vote(v),
+            BincodeInput::Feed(f) => NodeInput::Feed(f),
+            BincodeInput::Status(s) => NodeInput::Status(s),
+            BincodeInput::Reset => NodeInput::Reset,
+            BincodeInput::Stop => NodeInput::Stop,
+        };
+        Ok(input)
+    }
+
+    fn encode_output(&self, output: &NodeOutput) -> FrameResult<Vec<u8>> {
+        let output = match output.clone() {
+            NodeOutput::Proposal(p) => BincodeOutput::Proposal(p),
+            NodeOutput::Vote(v) => BincodeOutput::Vote(v),
+            NodeOutput::Commit(c) => BincodeOutput::Commit(c),
+        };
+        bincode::serialize(&output).map_err(codec_err)
+    }
+
+    fn decode_output(&self, bytes: &[u8]) -> FrameResult<NodeOutput> {
+        let output = match bincode::deserialize(bytes).map_err(codec_err)? {
+            BincodeOutput::Proposal(p) => NodeOutput::Proposal(p),
+            BincodeOutput::Vote(v) => NodeOutput::Vote(v),
+            BincodeOutput::Commit(c) => NodeOutput::Commit(c),
+        };
+        Ok(output)
+    }
+}
+
+pub(crate) fn codec_err<E: ToString>(e: E) -> FrameError {
+    FrameError::Codec(e.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::whitebox::types::*;
+
+    pub(crate) fn check_codec<C: Codec>(codec: C) {
+        let vote = Vote {
+            height: 2,
+            round: 1,
+            vote_type: VoteType::Precommit,
+            proposal: vec![1, 2],
+            voter: vec![3],
+        };
+        let inputs = vec![
+            NodeInput::Proposal(Proposal {
+                height: 2,
+                round: 2,
+                content: vec![1, 2],
+                proposer: vec![0],
+                lock_round: Some(1),
+                lock_votes: vec![vote.clone()],
+            }),
+            NodeInput::Vote(vote.clone()),
+            NodeInput::Feed(Feed {
+                height: 2,
+                proposal: vec![],
+            }),
+            NodeInput::Status(Status {
+                height: 1,
+                authority_list: vec![vec![0], vec![1, 1]],
+            }),
+            NodeInput::Reset,
+            NodeInput::Stop,
+        ];
+        for input in inputs.into_iter() {
+            let bytes = codec.encode_input(&input).unwrap();
+            assert_eq!(codec.decode_input(&bytes).unwrap(), input);
+        }
+
+        let outputs = vec![
+            NodeOutput::Proposal(Proposal {
+                height: 1,
+                round: 0,
+                content: vec![1],
+                proposer: vec![0],
+                lock_round: None,
+                lock_votes: Vec::new(),
+            }),
+            NodeOutput::Vote(vote),
+            NodeOutput::Commit(Commit {
+                node: 1,
+                height: 2,
+                result: vec![1, 2],
+            }),
+        ];
+        for output in outputs.into_iter() {
+            let bytes = codec.encode_output(&output).unwrap();
+            assert_eq!(codec.decode_output(&bytes).unwrap(), output);
+        }
+
+        assert!(codec.decode_output(&[0xff; 3]).is_err());
+    }
+
+    #[test]
+    fn test_json_codec() {
+        check_codec(JsonCodec);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode_codec() {
+        check_codec(BincodeCodec);
+    }
+}
@@ -0,0 +1,352 @@
+//! The protobuf codec, which is enabled by the `protobuf` feature. The messages are
+//! defined as below, where the `Reset` and the `Stop` inputs carry a `true`.
+//!
+//! ```protobuf
+//! syntax = "proto3";
+//!
+//! message Proposal {
+//!     uint64 height = 1;
+//!     uint64 round = 2;
+//!     bytes content = 3;
+//!     bytes proposer = 4;
+//!     // absent if the proposal has not been locked
+//!     optional uint64 lock_round = 5;
+//!     repeated Vote lock_votes = 6;
+//! }
+//!
+//! enum VoteType {
+//!     PREVOTE = 0;
+//!     PRECOMMIT = 1;
+//! }
+//!
+//! message Vote {
+//!     uint64 height = 1;
+//!     uint64 round = 2;
+//!     VoteType vote_type = 3;
+//!     bytes proposal = 4;
+//!     bytes voter = 5;
+//! }
+//!
+//! message Feed {
+//!     uint64 height = 1;
+//!     bytes proposal = 2;
+//! }
+//!
+//! message Status {
+//!     uint64 height = 1;
+//!     repeated bytes authority_list = 2;
+//! }
+//!
+//! message Commit {
+//!     uint32 node = 1;
+//!     uint64 height = 2;
+//!     bytes result = 3;
+//! }
+//!
+//! message NodeInput {
+//!     oneof msg {
+//!         Proposal proposal = 1;
+//!         Vote vote = 2;
+//!         Feed feed = 3;
+//!         Status status = 4;
+//!         bool reset = 5;
+//!         bool stop = 6;
+//!     }
+//! }
+//!
+//! message NodeOutput {
+//!     oneof msg {
+//!         Proposal proposal = 1;
+//!         Vote vote = 2;
+//!         Commit commit = 3;
+//!     }
+//! }
+//! ```
+
+use crate::error::FrameError;
+use crate::whitebox::{
+    transport::{
+        codec::{codec_err, Codec},
+        NodeInput, NodeOutput,
+    },
+    types::{Commit, Feed, FrameResult, Proposal, Status, Vote, VoteType},
+};
+
+use prost::{Enumeration, Message, Oneof};
+
+use std::convert::TryFrom;
+
+/// The protobuf codec.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProtobufCodec;
+
+#[derive(Clone, PartialEq, Message)]
+struct PbProposal {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(uint64, tag = "2")]
+    round: u64,
+    #[prost(bytes, tag = "3")]
+    content: Vec<u8>,
+    #[prost(bytes, tag = "4")]
+    proposer: Vec<u8>,
+    #[prost(uint64, optional, tag = "5")]
+    lock_round: Option<u64>,
+    #[prost(message, repeated, tag = "6")]
+    lock_votes: Vec<PbVote>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration)]
+#[repr(i32)]
+enum PbVoteType {
+    Prevote = 0,
+    Precommit = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbVote {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(uint64, tag = "2")]
+    round: u64,
+    #[prost(enumeration = "PbVoteType", tag = "3")]
+    vote_type: i32,
+    #[prost(bytes, tag = "4")]
+    proposal: Vec<u8>,
+    #[prost(bytes, tag = "5")]
+    voter: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbFeed {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(bytes, tag = "2")]
+    proposal: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbStatus {
+    #[prost(uint64, tag = "1")]
+    height: u64,
+    #[prost(bytes, repeated, tag = "2")]
+    authority_list: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbCommit {
+    #[prost(uint32, tag = "1")]
+    node: u32,
+    #[prost(uint64, tag = "2")]
+    height: u64,
+    #[prost(bytes, tag = "3")]
+    result: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbInput {
+    #[prost(oneof = "PbInputMsg", tags = "1, 2, 3, 4, 5, 6")]
+    msg: Option<PbInputMsg>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum PbInputMsg {
+    #[prost(message, tag = "1")]
+    Proposal(PbProposal),
+    #[prost(message, tag = "2")]
+    Vote(PbVote),
+    #[prost(message, tag = "3")]
+    Feed(PbFeed),
+    #[prost(message, tag = "4")]
+    Status(PbStatus),
+    #[prost(bool, tag = "5")]
+    Reset(bool),
+    #[prost(bool, tag = "6")]
+    Stop(bool),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbOutput {
+    #[prost(oneof = "PbOutputMsg", tags = "1, 2, 3")]
+    msg: Option<PbOutputMsg>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum PbOutputMsg {
+    #[prost(message, tag = "1")]
+    Proposal(PbProposal),
+    #[prost(message, tag = "2")]
+    Vote(PbVote),
+    #[prost(message, tag = "3")]
+    Commit(PbCommit),
+}
+
+impl From<Proposal> for PbProposal {
+    fn from(p: Proposal) -> Self {
+        PbProposal {
+            height: p.height,
+            round: p.round,
+            content: p.content,
+            proposer: p.proposer,
+            lock_round: p.lock_round,
+            lock_votes: p.lock_votes.into_iter().map(PbVote::from).collect(),
+        }
+    }
+}
+
+impl From<Vote> for PbVote {
+    fn from(v: Vote) -> Self {
+        let vote_type = match v.vote_type {
+            VoteType::Prevote => PbVoteType::Prevote,
+            VoteType::Precommit => PbVoteType::Precommit,
+        };
+        PbVote {
+            height: v.height,
+            round: v.round,
+            vote_type: vote_type as i32,
+            proposal: v.proposal,
+            voter: v.voter,
+        }
+    }
+}
+
+impl PbProposal {
+    fn into_proposal(self) -> FrameResult<Proposal> {
+        let lock_votes = self
+            .lock_votes
+            .into_iter()
+            .map(PbVote::into_vote)
+            .collect::<FrameResult<Vec<Vote>>>()?;
+        Ok(Proposal {
+            height: self.height,
+            round: self.round,
+            content: self.content,
+            proposer: self.proposer,
+            lock_round: self.lock_round,
+            lock_votes,
+        })
+    }
+}
+
+impl PbVote {
+    fn into_vote(self) -> FrameResult<Vote> {
+        let vote_type = match PbVoteType::from_i32(self.vote_type) {
+            Some(PbVoteType::Prevote) => VoteType::Prevote,
+            Some(PbVoteType::Precommit) => VoteType::Precommit,
+            None => {
+                return Err(FrameError::Codec(format!(
+                    "illegal vote type {}",
+                    self.vote_type
+                )))
+            }
+        };
+        Ok(Vote {
+            height: self.height,
+            round: self.round,
+            vote_type,
+            proposal: self.proposal,
+            voter: self.voter,
+        })
+    }
+}
+
+impl From<Commit> for PbCommit {
+    fn from(c: Commit) -> Self {
+        PbCommit {
+            node: u32::from(c.node),
+            height: c.height,
+            result: c.result,
+        }
+    }
+}
+
+impl PbCommit {
+    fn into_commit(self) -> FrameResult<Commit> {
+        let node = u8::try_from(self.node)
+            .map_err(|_| FrameError::Codec(format!("illegal node {}", self.node)))?;
+        Ok(Commit {
+            node,
+            height: self.height,
+            result: self.result,
+        })
+    }
+}
+
+fn encode<M: Message>(msg: &M) -> FrameResult<Vec<u8>> {
+    let mut buf = Vec::with_capacity(msg.encoded_len());
+    msg.encode(&mut buf).map_err(codec_err)?;
+    Ok(buf)
+}
+
+impl Codec for ProtobufCodec {
+    fn name(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn encode_input(&self, input: &NodeInput) -> FrameResult<Vec<u8>> {
+        let msg = match input.clone() {
+            NodeInput::Proposal(p) => PbInputMsg::Proposal(p.into()),
+            NodeInput::Vote(v) => PbInputMsg::Vote(v.into()),
+            NodeInput::Feed(f) => PbInputMsg::Feed(PbFeed {
+                height: f.height,
+                proposal: f.proposal,
+            }),
+            NodeInput::Status(s) => PbInputMsg::Status(PbStatus {
+                height: s.height,
+                authority_list: s.authority_list,
+            }),
+            NodeInput::Reset => PbInputMsg::Reset(true),
+            NodeInput::Stop => PbInputMsg::Stop(true),
+        };
+        encode(&PbInput { msg: Some(msg) })
+    }
+
+    fn decode_input(&self, bytes: &[u8]) -> FrameResult<NodeInput> {
+        let input = match PbInput::decode(bytes).map_err(codec_err)?.msg {
+            Some(PbInputMsg::Proposal(p)) => NodeInput::Proposal(p.into_proposal()?),
+            Some(PbInputMsg::Vote(v)) => NodeInput::Vote(v.into_vote()?),
+            Some(PbInputMsg::Feed(f)) => NodeInput::Feed(Feed {
+                height: f.height,
+                proposal: f.proposal,
+            }),
+            Some(PbInputMsg::Status(s)) => NodeInput::Status(Status {
+                height: s.height,
+                authority_list: s.authority_list,
+            }),
+            Some(PbInputMsg::Reset(_)) => NodeInput::Reset,
+            Some(PbInputMsg::Stop(_)) => NodeInput::Stop,
+            None => return Err(FrameError::Codec("empty node input".to_string())),
+        };
+        Ok(input)
+    }
+
+    fn encode_output(&self, output: &NodeOutput) -> FrameResult<Vec<u8>> {
+        let msg = match output.clone() {
+            NodeOutput::Proposal(p) => PbOutputMsg::Proposal(p.into()),
+            NodeOutput::Vote(v) => PbOutputMsg::Vote(v.into()),
+            NodeOutput::Commit(c) => PbOutputMsg::Commit(c.into()),
+        };
+        encode(&PbOutput { msg: Some(msg) })
+    }
+
+    fn decode_output(&self, bytes: &[u8]) -> FrameResult<NodeOutput> {
+        let output = match PbOutput::decode(bytes).map_err(codec_err)?.msg {
+            Some(PbOutputMsg::Proposal(p)) => NodeOutput::Proposal(p.into_proposal()?),
+            Some(PbOutputMsg::Vote(v)) => NodeOutput::Vote(v.into_vote()?),
+            Some(PbOutputMsg::Commit(c)) => NodeOutput::Commit(c.into_commit()?),
+            None => return Err(FrameError::Codec("empty node output".to_string())),
+        };
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::transport::codec::test::check_codec;
+
+    #[test]
+    fn test_protobuf_codec() {
+        check_codec(ProtobufCodec);
+    }
+}
@@ -0,0 +1,189 @@
+//! The RLP codec, which is enabled by the `rlp` feature. A message is encoded as a list
+//! of a kind byte and the fields of the message. The kind bytes of the inputs are
+//! `Proposal` 0, `Vote` 1, `Feed` 2, `Status` 3, `Reset` 4 and `Stop` 5, and the ones of
+//! the outputs are `Proposal` 0, `Vote` 1 and `Commit` 2.
+//!
+//! | message  | fields                                                            |
+//! |----------|-------------------------------------------------------------------|
+//! | Proposal | height, round, content, proposer, [lock round], [lock votes]      |
+//! | Vote     | height, round, vote type (prevote 0, precommit 1), proposal, voter |
+//! | Feed     | height, proposal                                                  |
+//! | Status   | height, [authorities]                                             |
+//! | Commit   | node, height, result                                              |
+//!
+//! The lock round list is empty if the proposal has not been locked.
+
+use crate::error::FrameError;
+use crate::whitebox::{
+    transport::{
+        codec::{codec_err, Codec},
+        NodeInput, NodeOutput,
+    },
+    types::{Commit, Feed, FrameResult, Proposal, Status, Vote, VoteType},
+};
+
+use rlp::{DecoderError, Rlp, RlpStream};
+
+/// The RLP codec.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RlpCodec;
+
+fn append_proposal(s: &mut RlpStream, p: &Proposal) {
+    s.append(&p.height).append(&p.round);
+    s.append(&p.content).append(&p.proposer);
+    match p.lock_round {
+        Some(round) => s.begin_list(1).append(&round),
+        None => s.begin_list(0),
+    };
+    s.begin_list(p.lock_votes.len());
+    for v in p.lock_votes.iter() {
+        s.begin_list(5);
+        append_vote(s, v);
+    }
+}
+
+fn append_vote(s: &mut RlpStream, v: &Vote) {
+    let vote_type: u8 = match v.vote_type {
+        VoteType::Prevote => 0,
+        VoteType::Precommit => 1,
+    };
+    s.append(&v.height).append(&v.round).append(&vote_type);
+    s.append(&v.proposal).append(&v.voter);
+}
+
+// Decode the fields of a message from the `offset` of the list.
+fn proposal_at(r: &Rlp, offset: usize) -> Result<Proposal, DecoderError> {
+    let lock_round = r.at(offset + 4)?.as_list::<u64>()?.pop();
+    let lock_votes = r
+        .at(offset + 5)?
+        .iter()
+        .map(|v| vote_at(&v, 0))
+        .collect::<Result<Vec<Vote>, DecoderError>>()?;
+    Ok(Proposal {
+        height: r.val_at(offset)?,
+        round: r.val_at(offset + 1)?,
+        content: r.val_at(offset + 2)?,
+        proposer: r.val_at(offset + 3)?,
+        lock_round,
+        lock_votes,
+    })
+}
+
+fn vote_at(r: &Rlp, offset: usize) -> Result<Vote, DecoderError> {
+    let vote_type = match r.val_at::<u8>(offset + 2)? {
+        0 => VoteType::Prevote,
+        1 => VoteType::Precommit,
+        _ => return Err(DecoderError::Custom("illegal vote type")),
+    };
+    Ok(Vote {
+        height: r.val_at(offset)?,
+        round: r.val_at(offset + 1)?,
+        vote_type,
+        proposal: r.val_at(offset + 3)?,
+        voter: r.val_at(offset + 4)?,
+    })
+}
+
+fn kind_of(r: &Rlp) -> FrameResult<u8> {
+    r.val_at(0).map_err(codec_err)
+}
+
+impl Codec for RlpCodec {
+    fn name(&self) -> &'static str {
+        "rlp"
+    }
+
+    fn encode_input(&self, input: &NodeInput) -> FrameResult<Vec<u8>> {
+        let mut s = RlpStream::new();
+        match input {
+            NodeInput::Proposal(p) => {
+                s.begin_list(7).append(&0u8);
+                append_proposal(&mut s, p);
+            }
+            NodeInput::Vote(v) => {
+                s.begin_list(6).append(&1u8);
+                append_vote(&mut s, v);
+            }
+            NodeInput::Feed(f) => {
+                s.begin_list(3).append(&2u8).append(&f.height);
+                s.append(&f.proposal);
+            }
+            NodeInput::Status(st) => {
+                s.begin_list(3).append(&3u8).append(&st.height);
+                s.append_list::<Vec<u8>, Vec<u8>>(&st.authority_list);
+            }
+            NodeInput::Reset => {
+                s.begin_list(1).append(&4u8);
+            }
+            NodeInput::Stop => {
+                s.begin_list(1).append(&5u8);
+            }
+        }
+        Ok(s.out())
+    }
+
+    fn decode_input(&self, bytes: &[u8]) -> FrameResult<NodeInput> {
+        let r = Rlp::new(bytes);
+        let input = match kind_of(&r)? {
+            0 => NodeInput::Proposal(proposal_at(&r, 1).map_err(codec_err)?),
+            1 => NodeInput::Vote(vote_at(&r, 1).map_err(codec_err)?),
+            2 => NodeInput::Feed(Feed {
+                height: r.val_at(1).map_err(codec_err)?,
+                proposal: r.val_at(2).map_err(codec_err)?,
+            }),
+            3 => NodeInput::Status(Status {
+                height: r.val_at(1).map_err(codec_err)?,
+                authority_list: r.list_at(2).map_err(codec_err)?,
+            }),
+            4 => NodeInput::Reset,
+            5 => NodeInput::Stop,
+            kind => return Err(FrameError::Codec(format!("illegal input kind {}", kind))),
+        };
+        Ok(input)
+    }
+
+    fn encode_output(&self, output: &NodeOutput) -> FrameResult<Vec<u8>> {
+        let mut s = RlpStream::new();
+        match output {
+            NodeOutput::Proposal(p) => {
+                s.begin_list(7).append(&0u8);
+                append_proposal(&mut s, p);
+            }
+            NodeOutput::Vote(v) => {
+                s.begin_list(6).append(&1u8);
+                append_vote(&mut s, v);
+            }
+            NodeOutput::Commit(c) => {
+                s.begin_list(4).append(&2u8).append(&c.node);
+                s.append(&c.height).append(&c.result);
+            }
+        }
+        Ok(s.out())
+    }
+
+    fn decode_output(&self, bytes: &[u8]) -> FrameResult<NodeOutput> {
+        let r = Rlp::new(bytes);
+        let output = match kind_of(&r)? {
+            0 => NodeOutput::Proposal(proposal_at(&r, 1).map_err(codec_err)?),
+            1 => NodeOutput::Vote(vote_at(&r, 1).map_err(codec_err)?),
+            2 => NodeOutput::Commit(Commit {
+                node: r.val_at(1).map_err(codec_err)?,
+                height: r.val_at(2).map_err(codec_err)?,
+                result: r.val_at(3).map_err(codec_err)?,
+            }),
+            kind => return Err(FrameError::Codec(format!("illegal output kind {}", kind))),
+        };
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::transport::codec::test::check_codec;
+
+    #[test]
+    fn test_rlp_codec() {
+        check_codec(RlpCodec);
+    }
+}
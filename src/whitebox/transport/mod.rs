@@ -0,0 +1,164 @@
+use crate::whitebox::{transport::codec::Codec, types::*};
+use crossbeam_channel::Sender;
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+///
+pub mod codec;
+///
+pub mod socket;
+///
+pub mod stdio;
+
+/// A message from the framework to an out-of-process testing node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum NodeInput {
+    /// Proposal message.
+    Proposal(Proposal),
+    /// Vote message.
+    Vote(Vote),
+    /// Proposal content message.
+    Feed(Feed),
+    /// Rich status message.
+    Status(Status),
+    /// Reset the testing node to a clean state.
+    Reset,
+    /// Stop the testing node.
+    Stop,
+}
+
+impl From<FrameSend> for NodeInput {
+    fn from(msg: FrameSend) -> Self {
+        match msg {
+            FrameSend::Proposal(p) => NodeInput::Proposal(p),
+            FrameSend::Vote(v) => NodeInput::Vote(v),
+            FrameSend::Feed(f) => NodeInput::Feed(f),
+            FrameSend::Status(s) => NodeInput::Status(s),
+        }
+    }
+}
+
+/// A message from an out-of-process testing node to the framework.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum NodeOutput {
+    /// Proposal message.
+    Proposal(Proposal),
+    /// Vote message.
+    Vote(Vote),
+    /// Commit message.
+    Commit(Commit),
+}
+
+/// A function to determine the proposer index by the height, the round and the length of
+/// authority list.
+pub type ProposerFn = Arc<dyn Fn(u64, u64, usize) -> usize + Send + Sync>;
+
+/// The default proposer function, which chooses the proposer by round robin.
+pub fn round_robin() -> ProposerFn {
+    Arc::new(|height, round, authority_num| {
+        ((height + round) % authority_num.max(1) as u64) as usize
+    })
+}
+
+// Decode a `NodeOutput` and dispatch it to the message or the commit channel. Illegal
+// outputs are logged and skipped.
+pub(crate) fn dispatch(
+    output: &[u8],
+    codec: &dyn Codec,
+    msgs: &Sender<FrameRecv>,
+    commits: &Sender<Commit>,
+) {
+    let res = match codec.decode_output(output) {
+        Ok(NodeOutput::Proposal(p)) => msgs.send(FrameRecv::Proposal(p)).is_ok(),
+        Ok(NodeOutput::Vote(v)) => msgs.send(FrameRecv::Vote(v)).is_ok(),
+        Ok(NodeOutput::Commit(c)) => commits.send(c).is_ok(),
+        Err(e) => {
+            warn!("Skip illegal node output of {} bytes: {}", output.len(), e);
+            true
+        }
+    };
+    if !res {
+        warn!("Drop node output after the node is dropped");
+    }
+}
+
+// The proposer election of a transport-based testing node. The length of authority list
+// is taken from the last status sent to the node.
+#[derive(Clone)]
+pub(crate) struct Proposer {
+    function: ProposerFn,
+    authority_num: Arc<AtomicUsize>,
+}
+
+impl Proposer {
+    pub(crate) fn new(function: ProposerFn) -> Self {
+        Proposer {
+            function,
+            authority_num: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub(crate) fn set_function(&mut self, function: ProposerFn) {
+        self.function = function;
+    }
+
+    pub(crate) fn observe(&self, msg: &FrameSend) {
+        if let FrameSend::Status(s) = msg {
+            self.authority_num
+                .store(s.authority_list.len(), Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn cal_proposer(&self, height: u64, round: u64) -> usize {
+        (self.function)(height, round, self.authority_num.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wire_format() {
+        let input = NodeInput::from(FrameSend::Feed(Feed {
+            height: 1,
+            proposal: vec![1, 2],
+        }));
+        assert_eq!(
+            serde_json::to_string(&input).unwrap(),
+            r#"{"type":"feed","data":{"height":1,"proposal":[1,2]}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&NodeInput::Stop).unwrap(),
+            r#"{"type":"stop"}"#
+        );
+
+        let output: NodeOutput =
+            serde_json::from_str(r#"{"type":"commit","data":{"node":0,"height":1,"result":[1]}}"#)
+                .unwrap();
+        assert_eq!(
+            output,
+            NodeOutput::Commit(Commit {
+                node: 0,
+                height: 1,
+                result: vec![1],
+            })
+        );
+    }
+
+    #[test]
+    fn test_proposer() {
+        let proposer = Proposer::new(round_robin());
+        proposer.observe(&FrameSend::Status(Status {
+            height: 0,
+            authority_list: vec![vec![0], vec![1], vec![2]],
+        }));
+        assert_eq!(proposer.cal_proposer(1, 0), 1);
+        assert_eq!(proposer.cal_proposer(1, 2), 0);
+    }
+}
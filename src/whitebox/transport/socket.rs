@@ -0,0 +1,438 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    transport::{
+        codec::{Codec, JsonCodec},
+        dispatch, round_robin, NodeInput, Proposer, ProposerFn,
+    },
+    types::*,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::{error, info, warn};
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// The max length of a frame, which is 64 MiB.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// A function to write a frame, which is a 4-byte big-endian length followed by the
+/// `payload`.
+pub fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes is too long", payload.len()),
+        ));
+    }
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// A function to read a frame written by `write_frame`.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too long", len),
+        ));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+// The write half of a connection, which can shut down both halves.
+trait Duplex: Write + Send {
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl Duplex for TcpStream {
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Duplex for UnixStream {
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+type Reader = Box<dyn Read + Send>;
+type Writer = Box<dyn Duplex>;
+type Connector = Box<dyn Fn() -> io::Result<(Reader, Writer)> + Send + Sync>;
+
+struct Connection {
+    // the generation of the connection, which increases when reconnecting
+    generation: u64,
+    writer: Option<Writer>,
+    stopped: bool,
+    reconnect_attempts: usize,
+    reconnect_interval: Duration,
+}
+
+impl Connection {
+    // Shut down the current connection, which stops its reading thread.
+    fn close(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.shutdown();
+        }
+    }
+}
+
+struct Inner {
+    name: String,
+    connector: Connector,
+    conn: Mutex<Connection>,
+    codec: RwLock<Arc<dyn Codec>>,
+    send_msg: Sender<FrameRecv>,
+    send_commit: Sender<Commit>,
+}
+
+/// A testing node running as a daemon, which is connected by a local TCP port or a Unix
+/// domain socket. The framework sends `NodeInput`s to the node and receives `NodeOutput`s
+/// from it, which are encoded by a `Codec` (JSON by default) and framed by a 4-byte
+/// big-endian length prefix.
+///
+/// The node is reconnected when the connection is broken, and the messages that can not
+/// be sent are dropped, which shows up as a missing vote or commit in the test.
+#[derive(Clone)]
+pub struct SocketNode {
+    inner: Arc<Inner>,
+    msgs: Receiver<FrameRecv>,
+    commits: Receiver<Commit>,
+    proposer: Proposer,
+}
+
+impl SocketNode {
+    /// A function to connect the testing node by a TCP address.
+    pub fn tcp<A: ToSocketAddrs>(addr: A) -> FrameResult<Self> {
+        let addrs = addr
+            .to_socket_addrs()
+            .map_err(|e| FrameError::Transport(format!("resolve address: {}", e)))?
+            .collect::<Vec<SocketAddr>>();
+        let name = format!("{:?}", addrs);
+        let connector: Connector = Box::new(move || {
+            let stream = TcpStream::connect(&addrs[..])?;
+            stream.set_nodelay(true)?;
+            let reader = stream.try_clone()?;
+            Ok((Box::new(reader) as Reader, Box::new(stream) as Writer))
+        });
+        SocketNode::connect(name, connector)
+    }
+
+    /// A function to connect the testing node by a Unix domain socket.
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P) -> FrameResult<Self> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let name = path.display().to_string();
+        let connector: Connector = Box::new(move || {
+            let stream = UnixStream::connect(&path)?;
+            let reader = stream.try_clone()?;
+            Ok((Box::new(reader) as Reader, Box::new(stream) as Writer))
+        });
+        SocketNode::connect(name, connector)
+    }
+
+    fn connect(name: String, connector: Connector) -> FrameResult<Self> {
+        let (send_msg, msgs) = unbounded();
+        let (send_commit, commits) = unbounded();
+        let node = SocketNode {
+            inner: Arc::new(Inner {
+                name,
+                connector,
+                conn: Mutex::new(Connection {
+                    generation: 0,
+                    writer: None,
+                    stopped: false,
+                    reconnect_attempts: 50,
+                    reconnect_interval: Duration::from_millis(100),
+                }),
+                codec: RwLock::new(Arc::new(JsonCodec)),
+                send_msg,
+                send_commit,
+            }),
+            msgs,
+            commits,
+            proposer: Proposer::new(round_robin()),
+        };
+        {
+            let mut conn = node.inner.conn.lock().unwrap();
+            Inner::open(&node.inner, &mut conn)?;
+        }
+        Ok(node)
+    }
+
+    /// A function to set how many times and how often to retry when reconnecting the
+    /// testing node. The default is 50 times every 100 milliseconds.
+    pub fn with_reconnect(self, attempts: usize, interval: Duration) -> Self {
+        {
+            let mut conn = self.inner.conn.lock().unwrap();
+            conn.reconnect_attempts = attempts;
+            conn.reconnect_interval = interval;
+        }
+        self
+    }
+
+    /// A function to set the codec of the messages. The default codec is `JsonCodec`.
+    pub fn with_codec<C: Codec>(self, codec: C) -> Self {
+        *self.inner.codec.write().unwrap() = Arc::new(codec);
+        self
+    }
+
+    /// A function to set the proposer function, which determines the proposer index by
+    /// the height, the round and the length of authority list. The default function is
+    /// round robin.
+    pub fn with_proposer(mut self, function: ProposerFn) -> Self {
+        self.proposer.set_function(function);
+        self
+    }
+
+    /// A function to send a message to the testing node. It reconnects the node and
+    /// resends the message once if the connection is broken.
+    pub fn write(&self, input: &NodeInput) -> FrameResult<()> {
+        let payload = self.inner.codec.read().unwrap().encode_input(input)?;
+        let mut conn = self.inner.conn.lock().unwrap();
+        if conn.stopped {
+            return Err(FrameError::Transport(format!(
+                "node {} is stopped",
+                self.inner.name
+            )));
+        }
+        if let Some(writer) = conn.writer.as_mut() {
+            match write_frame(writer, &payload) {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("Write node {} error {}", self.inner.name, e),
+            }
+        }
+        Inner::reconnect(&self.inner, &mut conn)?;
+        let writer = conn.writer.as_mut().expect("a connected writer");
+        write_frame(writer, &payload).map_err(|e| {
+            conn.close();
+            FrameError::Transport(format!("write node {}: {}", self.inner.name, e))
+        })
+    }
+}
+
+impl Inner {
+    // Open a new connection and spawn a thread to read from it.
+    fn open(this: &Arc<Inner>, conn: &mut Connection) -> FrameResult<()> {
+        let (mut reader, writer) = (this.connector)()
+            .map_err(|e| FrameError::Transport(format!("connect node {}: {}", this.name, e)))?;
+        conn.close();
+        conn.generation += 1;
+        conn.writer = Some(writer);
+
+        let generation = conn.generation;
+        let inner = Arc::downgrade(this);
+        thread::spawn(move || loop {
+            let frame = read_frame(&mut reader);
+            // stop reading when the node is dropped
+            let inner = match inner.upgrade() {
+                Some(inner) => inner,
+                None => break,
+            };
+            match frame {
+                Ok(frame) => {
+                    let codec = Arc::clone(&inner.codec.read().unwrap());
+                    dispatch(&frame, &*codec, &inner.send_msg, &inner.send_commit);
+                }
+                Err(e) => {
+                    let mut conn = inner.conn.lock().unwrap();
+                    if conn.generation == generation && !conn.stopped {
+                        info!("Connection to node {} is broken: {}", inner.name, e);
+                        conn.close();
+                    }
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn reconnect(this: &Arc<Inner>, conn: &mut Connection) -> FrameResult<()> {
+        let mut res = Inner::open(this, conn);
+        for _ in 1..conn.reconnect_attempts {
+            if res.is_ok() {
+                break;
+            }
+            thread::sleep(conn.reconnect_interval);
+            res = Inner::open(this, conn);
+        }
+        res
+    }
+}
+
+impl Support for SocketNode {
+    fn send(&self, msg: FrameSend) {
+        self.proposer.observe(&msg);
+        if let Err(e) = self.write(&msg.into()) {
+            error!("Send to node error {}", e);
+        }
+    }
+
+    fn recv(&self) -> FrameRecv {
+        // the sender is never dropped before the node, so it blocks until a message
+        loop {
+            if let Ok(msg) = self.msgs.recv() {
+                return msg;
+            }
+        }
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Option<FrameRecv> {
+        self.msgs.recv_timeout(timeout).ok()
+    }
+
+    fn try_get_commit(&self) -> Option<Commit> {
+        self.commits.try_recv().ok()
+    }
+
+    fn wait_commit(&self, timeout: Duration) -> Option<Commit> {
+        self.commits.recv_timeout(timeout).ok()
+    }
+
+    fn stop(&self) {
+        if let Err(e) = self.write(&NodeInput::Stop) {
+            warn!("Send stop to node error {}", e);
+        }
+        let mut conn = self.inner.conn.lock().unwrap();
+        conn.stopped = true;
+        conn.close();
+    }
+
+    fn cal_proposer(&self, height: u64, round: u64) -> usize {
+        self.proposer.cal_proposer(height, round)
+    }
+
+    fn reset(&self) {
+        if let Err(e) = self.write(&NodeInput::Reset) {
+            error!("Send reset to node error {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::whitebox::transport::NodeOutput;
+    use std::net::TcpListener;
+
+    // A node that commits every feed, and closes the connection after a reset.
+    fn serve<S: Read + Write, C: Codec>(mut stream: S, codec: C) {
+        while let Ok(frame) = read_frame(&mut stream) {
+            let output = match codec.decode_input(&frame).unwrap() {
+                NodeInput::Feed(f) => NodeOutput::Commit(Commit {
+                    node: 0,
+                    height: f.height,
+                    result: f.proposal,
+                }),
+                NodeInput::Vote(v) => NodeOutput::Vote(v),
+                NodeInput::Reset | NodeInput::Stop => return,
+                _ => continue,
+            };
+            write_frame(&mut stream, &codec.encode_output(&output).unwrap()).unwrap();
+        }
+    }
+
+    fn check(node: SocketNode) {
+        let vote = Vote {
+            height: 1,
+            round: 0,
+            vote_type: VoteType::Precommit,
+            proposal: vec![1],
+            voter: vec![0],
+        };
+        node.send(FrameSend::Vote(vote.clone()));
+        assert_eq!(
+            node.recv_timeout(Duration::from_secs(5)),
+            Some(FrameRecv::Vote(vote))
+        );
+
+        // the node closes the connection, and it is reconnected by the next message
+        node.reset();
+        thread::sleep(Duration::from_millis(50));
+        node.send(FrameSend::Feed(Feed {
+            height: 1,
+            proposal: vec![2],
+        }));
+        assert_eq!(
+            node.wait_commit(Duration::from_secs(5)).map(|c| c.result),
+            Some(vec![2])
+        );
+        node.stop();
+        assert!(node.write(&NodeInput::Reset).is_err());
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"abc").unwrap();
+        assert_eq!(buf, vec![0, 0, 0, 3, b'a', b'b', b'c']);
+        assert_eq!(read_frame(&mut &buf[..]).unwrap(), b"abc".to_vec());
+        assert!(read_frame(&mut &buf[..5]).is_err());
+        assert!(read_frame(&mut &[0xff, 0xff, 0xff, 0xff][..]).is_err());
+    }
+
+    #[test]
+    fn test_tcp_node() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap(), JsonCodec);
+            }
+        });
+        check(SocketNode::tcp(addr).unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(SocketNode::tcp(addr).is_err());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_tcp_node_with_codec() {
+        use crate::whitebox::transport::codec::BincodeCodec;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap(), BincodeCodec);
+            }
+        });
+        check(SocketNode::tcp(addr).unwrap().with_codec(BincodeCodec));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_node() {
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("bft_test_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap(), JsonCodec);
+            }
+        });
+        check(SocketNode::unix(&path).unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::error::FrameError;
+use crate::whitebox::{
+    transport::{codec::JsonCodec, dispatch, round_robin, NodeInput, Proposer, ProposerFn},
+    types::*,
+};
+
+use crossbeam_channel::{unbounded, Receiver};
+use log::{error, warn};
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A testing node running as a child process. The framework writes a `NodeInput` to its
+/// stdin and reads a `NodeOutput` from its stdout per line, both of which are JSON. For
+/// example, the node reads
+///
+/// ```json
+/// {"type":"feed","data":{"height":1,"proposal":[1,2,3]}}
+/// ```
+///
+/// and writes
+///
+/// ```json
+/// {"type":"commit","data":{"node":0,"height":1,"result":[1,2,3]}}
+/// ```
+///
+/// The stderr of the node is inherited. Lines that can not be parsed are logged and
+/// skipped. The protocol is always JSON, use a `SocketNode` for other codecs.
+#[derive(Clone)]
+pub struct StdioNode {
+    child: Arc<Mutex<Child>>,
+    stdin: Arc<Mutex<ChildStdin>>,
+    msgs: Receiver<FrameRecv>,
+    commits: Receiver<Commit>,
+    proposer: Proposer,
+}
+
+impl StdioNode {
+    /// A function to spawn the testing node by the `command`. The stdin and the stdout of
+    /// the command are replaced by pipes.
+    pub fn spawn(mut command: Command) -> FrameResult<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| FrameError::Transport(format!("spawn {:?}: {}", command, e)))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| FrameError::Transport("no stdin of node".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| FrameError::Transport("no stdout of node".to_string()))?;
+
+        let (send_msg, msgs) = unbounded();
+        let (send_commit, commits) = unbounded();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(ref line) if line.trim().is_empty() => (),
+                    Ok(line) => dispatch(line.as_bytes(), &JsonCodec, &send_msg, &send_commit),
+                    Err(e) => {
+                        error!("Read node stdout error {:?}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(StdioNode {
+            child: Arc::new(Mutex::new(child)),
+            stdin: Arc::new(Mutex::new(stdin)),
+            msgs,
+            commits,
+            proposer: Proposer::new(round_robin()),
+        })
+    }
+
+    /// A function to set the proposer function, which determines the proposer index by
+    /// the height, the round and the length of authority list. The default function is
+    /// round robin.
+    pub fn with_proposer(mut self, function: ProposerFn) -> Self {
+        self.proposer.set_function(function);
+        self
+    }
+
+    /// A function to write a message to the stdin of the testing node.
+    pub fn write(&self, input: &NodeInput) -> FrameResult<()> {
+        let mut line = serde_json::to_string(input)
+            .map_err(|e| FrameError::Transport(format!("encode {:?}: {}", input, e)))?;
+        line.push('\n');
+        let mut stdin = self.stdin.lock().unwrap();
+        stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| stdin.flush())
+            .map_err(|e| FrameError::Transport(format!("write node stdin: {}", e)))
+    }
+}
+
+impl Support for StdioNode {
+    fn send(&self, msg: FrameSend) {
+        self.proposer.observe(&msg);
+        if let Err(e) = self.write(&msg.into()) {
+            error!("Send to node error {}", e);
+        }
+    }
+
+    fn recv(&self) -> FrameRecv {
+        self.msgs
+            .recv()
+            .unwrap_or_else(|_| panic!("The stdout of node is closed!"))
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Option<FrameRecv> {
+        self.msgs.recv_timeout(timeout).ok()
+    }
+
+    fn try_get_commit(&self) -> Option<Commit> {
+        self.commits.try_recv().ok()
+    }
+
+    fn wait_commit(&self, timeout: Duration) -> Option<Commit> {
+        self.commits.recv_timeout(timeout).ok()
+    }
+
+    fn stop(&self) {
+        if let Err(e) = self.write(&NodeInput::Stop) {
+            warn!("Send stop to node error {}", e);
+        }
+        // kill the node if it does not exit in 1 second
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut child = self.child.lock().unwrap();
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(_)) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(_) => break,
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    fn cal_proposer(&self, height: u64, round: u64) -> usize {
+        self.proposer.cal_proposer(height, round)
+    }
+
+    fn reset(&self) {
+        if let Err(e) = self.write(&NodeInput::Reset) {
+            error!("Send reset to node error {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stdio_node() {
+        // `cat` echoes the proposals and the votes as the node output
+        let node = StdioNode::spawn(Command::new("cat")).unwrap();
+        let vote = Vote {
+            height: 1,
+            round: 0,
+            vote_type: VoteType::Prevote,
+            proposal: vec![1],
+            voter: vec![0],
+        };
+        node.send(FrameSend::Feed(Feed {
+            height: 1,
+            proposal: vec![1],
+        }));
+        node.send(FrameSend::Vote(vote.clone()));
+        assert_eq!(
+            node.recv_timeout(Duration::from_secs(5)),
+            Some(FrameRecv::Vote(vote))
+        );
+        assert_eq!(node.recv_timeout(Duration::from_millis(10)), None);
+        assert_eq!(node.try_get_commit(), None);
+
+        node.send(FrameSend::Status(Status {
+            height: 1,
+            authority_list: vec![vec![0], vec![1], vec![2], vec![3]],
+        }));
+        assert_eq!(node.cal_proposer(2, 1), 3);
+        node.stop();
+
+        assert!(StdioNode::spawn(Command::new("/nonexistent/node")).is_err());
+    }
+}
@@ -1,6 +1,9 @@
 use crate::error::{BftError, FrameError};
 use serde_derive::{Deserialize, Serialize};
 
+use std::thread;
+use std::time::{Duration, Instant};
+
 pub(crate) type Hash = Vec<u8>;
 pub(crate) type Address = Vec<u8>;
 /// BFT result.
@@ -106,12 +109,37 @@ pub trait Support {
     fn send(&self, msg: FrameSend);
     /// Receive a `FrameRecv` message from the testing node.
     fn recv(&self) -> FrameRecv;
+    /// Receive a `FrameRecv` message from the testing node in the `timeout`.
+    /// If there is no message before the timeout, return `None`, so that a missing
+    /// message fails the test rather than hangs it.
+    fn recv_timeout(&self, timeout: Duration) -> Option<FrameRecv>;
     /// Try once to get a commit message from the testing node.
     /// If it does not commit, return `None`.
     fn try_get_commit(&self) -> Option<Commit>;
+    /// Wait for a commit message from the testing node until the `timeout`. If it does
+    /// not commit before the timeout, return `None`. The default implementation
+    /// busy-polls `try_get_commit` every 1 ms until the timeout, override it if the
+    /// commit can be blocked on.
+    fn wait_commit(&self, timeout: Duration) -> Option<Commit> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(commit) = self.try_get_commit() {
+                return Some(commit);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(1)));
+        }
+    }
     /// Stop the testing node.
     fn stop(&self);
     /// Determine the proposer index in the authority list by
     /// the given height and round.
     fn cal_proposer(&self, height: u64, round: u64) -> usize;
+    /// Reset the testing node to a clean state. It is called between the runs of model
+    /// checking, and the testing node receives a status to start after reset. The default
+    /// implementation does nothing.
+    fn reset(&self) {}
 }